@@ -25,6 +25,8 @@ const ProcessRow = ({ process, isParent }) => {
     // Update to match exact attribute names
     const threatScore = process.attributes?.ThreatScore;
     const threatReason = process.attributes?.ThreatScoreReason;
+    const chain = process.attributes?.ProcessChain;
+    const lineage = process.attributes?.SuspiciousLineage;
 
     return (
         <div className={`p-4 w-full ${isParent ? 'bg-slate-800' : 'bg-slate-900'} 
@@ -57,6 +59,12 @@ const ProcessRow = ({ process, isParent }) => {
                     )}
                 </div>
             </div>
+            {chain && (
+                <div className="mt-1 text-xs font-mono text-slate-400">
+                    {chain}
+                    {lineage && <span className="ml-2 text-orange-400">{lineage}</span>}
+                </div>
+            )}
         </div>
     );
 };
@@ -247,8 +255,8 @@ const SystemDashboard = ({ data, loading }) => {
                     <MetricCard
                         title="Network"
                         icon={Network}
-                        value={`${(networkRx / 1024 / 1024).toFixed(1)} MB`}
-                        subValue={`${(networkTx / 1024 / 1024).toFixed(1)} MB TX`}
+                        value={`${(networkRx / 1024 / 1024).toFixed(1)} MB/s`}
+                        subValue={`${(networkTx / 1024 / 1024).toFixed(1)} MB/s TX`}
                         color="text-purple-400"
                     />
                 </div>
@@ -258,7 +266,7 @@ const SystemDashboard = ({ data, loading }) => {
 
                 {/* Process List */}
                 <div className="w-full border border-slate-700 rounded-lg overflow-hidden shadow-xl shadow-black/20 mt-6">
-                    {data.processes?.map(({ parent_process, forked_threads }) => (
+                    {data.processes?.map(({ parent_process, children }) => (
                         <div key={parent_process.pid} className="w-full border-b border-slate-700 last:border-b-0">
                             <button
                                 className="w-full text-left"
@@ -275,10 +283,10 @@ const SystemDashboard = ({ data, loading }) => {
 
                             {expandedProcesses.has(parent_process.pid) && (
                                 <div className="ml-4 w-full">
-                                    {forked_threads.map(thread => (
+                                    {children.map(child => (
                                         <ProcessRow
-                                            key={thread.pid}
-                                            process={thread}
+                                            key={child.pid}
+                                            process={child}
                                             isParent={false}
                                         />
                                     ))}
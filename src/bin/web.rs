@@ -6,6 +6,6 @@ use odin::{
 #[tokio::main]
 async fn main() {
     let settings = get_configuration().expect("Failed to read configuration.");
-    let monitor = SystemMonitor::new(settings.clone());
+    let monitor = SystemMonitor::new(settings.clone()).expect("Invalid monitor configuration.");
     start_server(monitor).await
 }
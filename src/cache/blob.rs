@@ -1,12 +1,45 @@
-use std::collections::HashMap;
-use std::fmt::Debug;
+use std::collections::{BTreeMap, HashMap};
 use std::hash::Hash;
+use std::sync::{Arc, Weak};
 use std::time::{Duration, Instant};
 
+use serde::Serialize;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+#[derive(Debug)]
+struct Entry<V> {
+    value: V,
+    inserted: Instant,
+    // Position in the lru index, bumped on every hit
+    last_access: u64,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    // Entries pushed out to make room for new ones
+    pub evictions: u64,
+    // Entries dropped because their ttl ran out
+    pub expirations: u64,
+}
+
+/**
+ Generic TTL cache with an optional max entry count.
+
+ When full the least recently used entry is evicted. Expired entries are dropped lazily on read,
+ by `remove_expired`, or periodically by a sweeper started with `spawn_sweeper`.
+**/
 #[derive(Debug)]
 pub struct Cache<K, V> {
-    data: HashMap<K, (V, Instant)>,
+    data: HashMap<K, Entry<V>>,
+    // Access counter to key, the first entry is always the least recently used
+    lru: BTreeMap<u64, K>,
+    access_counter: u64,
     ttl: Duration,
+    capacity: Option<usize>,
+    stats: CacheStats,
 }
 
 impl<K, V> Cache<K, V>
@@ -17,41 +50,179 @@ where
     pub fn new(ttl_seconds: u64) -> Self {
         Self {
             data: HashMap::new(),
+            lru: BTreeMap::new(),
+            access_counter: 0,
             ttl: Duration::from_secs(ttl_seconds),
+            capacity: None,
+            stats: CacheStats::default(),
+        }
+    }
+
+    pub fn with_capacity(ttl_seconds: u64, max_entries: usize) -> Self {
+        Self {
+            capacity: Some(max_entries.max(1)),
+            ..Self::new(ttl_seconds)
         }
     }
 
     pub fn insert(&mut self, key: K, value: V) {
-        self.data.insert(key, (value, Instant::now()));
+        let last_access = self.next_access();
+        if let Some(previous) = self.data.get(&key) {
+            self.lru.remove(&previous.last_access);
+        } else if self.capacity.is_some_and(|capacity| self.data.len() >= capacity) {
+            // Expired entries are left to the sweeper, scanning for them here would make inserts O(n)
+            self.evict_lru();
+        }
+        self.lru.insert(last_access, key.clone());
+        self.data.insert(
+            key,
+            Entry {
+                value,
+                inserted: Instant::now(),
+                last_access,
+            },
+        );
     }
 
-    pub fn get(&self, key: &K) -> Option<V> {
-        self.data.get(key).and_then(|(value, timestamp)| {
-            if timestamp.elapsed() <= self.ttl {
-                Some(value.clone())
-            } else {
-                None
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        let expired = match self.data.get(key) {
+            Some(entry) => entry.inserted.elapsed() > self.ttl,
+            None => {
+                self.stats.misses += 1;
+                return None;
             }
-        })
+        };
+        if expired {
+            self.remove(key);
+            self.stats.expirations += 1;
+            self.stats.misses += 1;
+            return None;
+        }
+
+        let last_access = self.next_access();
+        let entry = self.data.get_mut(key)?;
+        self.lru.remove(&entry.last_access);
+        self.lru.insert(last_access, key.clone());
+        entry.last_access = last_access;
+        self.stats.hits += 1;
+        Some(entry.value.clone())
     }
-    pub fn remove_expired(&mut self) {
-        self.data
-            .retain(|_, (_, timestamp)| timestamp.elapsed() <= self.ttl);
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let entry = self.data.remove(key)?;
+        self.lru.remove(&entry.last_access);
+        Some(entry.value)
+    }
+
+    /// Drops every expired entry and returns how many were removed
+    pub fn remove_expired(&mut self) -> usize {
+        let ttl = self.ttl;
+        let expired: Vec<K> = self
+            .data
+            .iter()
+            .filter(|(_, entry)| entry.inserted.elapsed() > ttl)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &expired {
+            self.remove(key);
+        }
+        self.stats.expirations += expired.len() as u64;
+        expired.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    fn evict_lru(&mut self) {
+        if let Some((_, key)) = self.lru.pop_first() {
+            self.data.remove(&key);
+            self.stats.evictions += 1;
+        }
+    }
+
+    fn next_access(&mut self) -> u64 {
+        self.access_counter += 1;
+        self.access_counter
     }
 }
 
-pub fn get_cached_data<K, V>(cache: &Cache<K, V>) -> Vec<V>
+impl<K, V> Cache<K, V>
 where
-    K: Hash + Eq + Clone + Ord, // Add Ord since we need to sort
-    V: Clone + Debug,
+    K: Hash + Eq + Clone + Send + 'static,
+    V: Clone + Send + 'static,
 {
-    // Get all entries and sort by key (timestamp) in reverse order
-    let mut entries: Vec<_> = cache.data.keys().cloned().collect();
-    entries.sort_by(|a, b| b.cmp(a)); // Reverse sort so newest is first
-    
-    // Take up to 5 most recent entries
-    entries.iter()
-        .take(5)
-        .filter_map(|key| cache.get(key))
-        .collect()
-}
\ No newline at end of file
+    /// Periodically removes expired entries, the task ends once the cache is dropped
+    pub fn spawn_sweeper(cache: &Arc<Mutex<Self>>, interval: Duration) -> JoinHandle<()> {
+        let cache: Weak<Mutex<Self>> = Arc::downgrade(cache);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let Some(cache) = cache.upgrade() else {
+                    break;
+                };
+                let removed = cache.lock().await.remove_expired();
+                if removed > 0 {
+                    tracing::debug!("Cache sweeper removed {} expired entries", removed);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let mut cache = Cache::with_capacity(60, 2);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        // Touch a so b becomes the oldest
+        assert_eq!(cache.get(&"a"), Some(1));
+        cache.insert("c", 3);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"a"), Some(1));
+        assert_eq!(cache.get(&"c"), Some(3));
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 3,
+                misses: 1,
+                evictions: 1,
+                expirations: 0,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cache_sweeper_removes_expired() {
+        let cache = Arc::new(Mutex::new(Cache::new(1)));
+        cache.lock().await.insert("a", 1);
+        let sweeper = Cache::spawn_sweeper(&cache, Duration::from_millis(100));
+
+        tokio::time::sleep(Duration::from_millis(1300)).await;
+        let cache_guard = cache.lock().await;
+        assert!(cache_guard.is_empty());
+        assert_eq!(cache_guard.stats().expirations, 1);
+        drop(cache_guard);
+
+        drop(cache);
+        tokio::time::timeout(Duration::from_secs(1), sweeper)
+            .await
+            .expect("Sweeper should stop once the cache is dropped")
+            .unwrap();
+    }
+}
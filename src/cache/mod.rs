@@ -1,3 +1,5 @@
 pub mod blob;
+pub mod ring;
 
 pub use blob::*;
+pub use ring::*;
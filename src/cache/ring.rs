@@ -0,0 +1,132 @@
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Utc};
+
+use crate::storage::Sample;
+
+/**
+ Fixed capacity buffer of samples kept in timestamp order.
+
+ Once full the oldest sample is dropped for every new one. Because samples are sorted, latest is
+ the back of the deque and range lookups are two binary searches.
+**/
+#[derive(Debug)]
+pub struct RingBuffer<T> {
+    samples: VecDeque<Sample<T>>,
+    capacity: usize,
+}
+
+impl<T> RingBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, sample: Sample<T>) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        match self.samples.back() {
+            Some(last) if sample.timestamp < last.timestamp => {
+                // Clock went backwards, keep the buffer sorted
+                let idx = self
+                    .samples
+                    .partition_point(|s| s.timestamp <= sample.timestamp);
+                self.samples.insert(idx, sample);
+            }
+            _ => self.samples.push_back(sample),
+        }
+    }
+
+    pub fn latest(&self) -> Option<&Sample<T>> {
+        self.samples.back()
+    }
+
+    /// Samples with `from <= timestamp <= to`, oldest first
+    pub fn range(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> impl DoubleEndedIterator<Item = &Sample<T>> {
+        let start = self.samples.partition_point(|s| s.timestamp < from);
+        let end = self.samples.partition_point(|s| s.timestamp <= to).max(start);
+        self.samples.range(start..end)
+    }
+
+    /// Every sample oldest first
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Sample<T>> {
+        self.samples.iter()
+    }
+
+    /// Up to `count` samples newest first
+    pub fn recent(&self, count: usize) -> impl Iterator<Item = &Sample<T>> {
+        self.samples.iter().rev().take(count)
+    }
+
+    /// Drops every sample older than `cutoff`
+    pub fn evict_before(&mut self, cutoff: DateTime<Utc>) {
+        let expired = self.samples.partition_point(|s| s.timestamp < cutoff);
+        self.samples.drain(..expired);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn sample_at(base: DateTime<Utc>, seconds: i64, value: u32) -> Sample<u32> {
+        Sample {
+            timestamp: base + Duration::seconds(seconds),
+            value,
+        }
+    }
+
+    #[test]
+    fn test_ring_buffer_drops_oldest_when_full() {
+        let base = Utc::now();
+        let mut buffer = RingBuffer::new(3);
+        for i in 0..5 {
+            buffer.push(sample_at(base, i, i as u32));
+        }
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.latest().map(|s| s.value), Some(4));
+        let recent: Vec<u32> = buffer.recent(10).map(|s| s.value).collect();
+        assert_eq!(recent, vec![4, 3, 2]);
+    }
+
+    #[test]
+    fn test_ring_buffer_range_and_out_of_order_push() {
+        let base = Utc::now();
+        let mut buffer = RingBuffer::new(10);
+        for i in [0, 10, 20, 40] {
+            buffer.push(sample_at(base, i, i as u32));
+        }
+        buffer.push(sample_at(base, 30, 30));
+
+        let values: Vec<u32> = buffer
+            .range(base + Duration::seconds(10), base + Duration::seconds(30))
+            .map(|s| s.value)
+            .collect();
+        assert_eq!(values, vec![10, 20, 30]);
+        assert_eq!(buffer.latest().map(|s| s.value), Some(40));
+
+        buffer.evict_before(base + Duration::seconds(25));
+        assert_eq!(buffer.len(), 2);
+    }
+}
@@ -0,0 +1,607 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use metrics::counter;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::{
+    sync::Mutex as AsyncMutex,
+    task::{JoinHandle, JoinSet},
+    time::Instant,
+};
+
+use crate::{
+    monitor::MetricStore,
+    storage::{downsample, Resolution, Rollup, RollupMetrics, Sample},
+};
+
+/**
+ A source of samples the monitor polls on a fixed interval.
+
+ `collect` is plain blocking code (sysinfo refreshes, /proc walks) and always runs on a blocking
+ thread, so collectors keep whatever state they need between ticks in `self` rather than sleeping
+ to measure a delta.
+**/
+pub trait Collector: Send + 'static {
+    type Output: Clone + Debug + Serialize + DeserializeOwned + Send + Sync + 'static;
+
+    // Also the store key, the snapshot field and the /history resource
+    fn name(&self) -> &'static str;
+    fn interval(&self) -> Duration;
+    // Anything slower than one interval is already late for the next tick
+    fn timeout(&self) -> Duration {
+        self.interval()
+    }
+    fn collect(&mut self) -> Result<Self::Output>;
+}
+
+/// Reacts to every value a collector produces, before it is stored
+#[async_trait]
+pub trait CollectorObserver<T>: Send + Sync {
+    async fn observe(&self, value: &T);
+}
+
+/**
+ Runs one collector's blocking work on tokio's blocking pool so it can never stall the runtime
+ serving the API.
+
+ The collector's state moves onto the blocking thread for each run and comes back with the result,
+ panics included. A run that outlives `timeout` is left to finish in the background, the collector
+ keeps skipping ticks until it does rather than piling up more threads stuck on the same slow read.
+**/
+pub struct BlockingCollector<S, T> {
+    name: &'static str,
+    timeout: Duration,
+    // None while a run has the state
+    state: Option<S>,
+    pending: Option<JoinHandle<(S, std::thread::Result<T>)>>,
+}
+
+impl<S, T> BlockingCollector<S, T>
+where
+    S: Send + 'static,
+    T: Send + 'static,
+{
+    pub fn new(name: &'static str, state: S, timeout: Duration) -> Self {
+        Self {
+            name,
+            timeout,
+            state: Some(state),
+            pending: None,
+        }
+    }
+
+    /// None when the run timed out, panicked or an earlier run is still going
+    pub async fn collect<F>(&mut self, collect: F) -> Option<T>
+    where
+        F: FnOnce(&mut S) -> T + Send + 'static,
+    {
+        if let Some(pending) = self.pending.take() {
+            if !pending.is_finished() {
+                tracing::warn!("{} collection still running, skipping this tick", self.name);
+                self.pending = Some(pending);
+                return None;
+            }
+            // Too late to be useful, only the state is worth keeping
+            self.finish(pending.await);
+        }
+
+        let mut state = self.state.take()?;
+        let mut handle = tokio::task::spawn_blocking(move || {
+            let output = panic::catch_unwind(AssertUnwindSafe(|| collect(&mut state)));
+            (state, output)
+        });
+        match tokio::time::timeout(self.timeout, &mut handle).await {
+            Ok(result) => self.finish(result),
+            Err(_) => {
+                tracing::warn!("{} collection timed out after {:?}", self.name, self.timeout);
+                counter!("collector.timeout", "collector" => self.name).increment(1);
+                self.pending = Some(handle);
+                None
+            }
+        }
+    }
+
+    fn finish(
+        &mut self,
+        result: Result<(S, std::thread::Result<T>), tokio::task::JoinError>,
+    ) -> Option<T> {
+        // Blocking tasks can't be cancelled and panics are caught inside, so the state always returns
+        let (state, output) = result.ok()?;
+        self.state = Some(state);
+        match output {
+            Ok(output) => Some(output),
+            Err(_) => {
+                tracing::error!("{} collection panicked", self.name);
+                counter!("collector.panic", "collector" => self.name).increment(1);
+                None
+            }
+        }
+    }
+}
+
+// Restarts of a collector that keeps stopping wait twice as long each time, up to the max
+const RESTART_BACKOFF_MIN: Duration = Duration::from_secs(1);
+const RESTART_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+type Runner<C> = BlockingCollector<C, Result<<C as Collector>::Output>>;
+
+/// Ticks the collector in `runner` forever, failed and skipped ticks are logged and the next one tried
+pub async fn supervise<C: Collector>(
+    runner: Arc<AsyncMutex<Runner<C>>>,
+    interval: Duration,
+    store: Arc<MetricStore<C::Output>>,
+    observer: Observer<C::Output>,
+) {
+    // Held for as long as this run lives, a panic or abort releases it so a restart keeps the state
+    let mut runner = runner.lock().await;
+    let name = runner.name;
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        counter!("collector.run", "collector" => name).increment(1);
+        match runner.collect(|collector| collector.collect()).await {
+            Some(Ok(value)) => {
+                if let Some(observer) = &observer {
+                    observer.observe(&value).await;
+                }
+                store.store(name, value).await;
+            }
+            Some(Err(e)) => tracing::error!("Failed to collect {} metrics: {}", name, e),
+            None => {}
+        }
+    }
+}
+
+// Type erased view of a registered store, what snapshots, replay and the API go through
+#[async_trait]
+trait RegisteredStore: Send + Sync {
+    // The Arc<MetricStore<T>> itself, for typed access
+    fn as_any(&self) -> &dyn Any;
+    async fn latest_json(&self) -> Option<serde_json::Value>;
+    async fn store_json(&self, name: &str, sample: Sample<serde_json::Value>) -> Result<()>;
+    // None when the store keeps no history
+    async fn history(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        step: Option<i64>,
+    ) -> Option<Result<Vec<Sample<RollupMetrics>>>>;
+    async fn run_maintenance(&self) -> Result<()>;
+}
+
+struct Store<T>(Arc<MetricStore<T>>);
+// Stores whose samples roll up into numeric history
+struct RollupStore<T>(Store<T>);
+
+#[async_trait]
+impl<T> RegisteredStore for Store<T>
+where
+    T: Clone + Debug + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    fn as_any(&self) -> &dyn Any {
+        &self.0
+    }
+
+    async fn latest_json(&self) -> Option<serde_json::Value> {
+        let latest = self.0.get_recent(1).await.pop()?;
+        serde_json::to_value(latest).ok()
+    }
+
+    async fn store_json(&self, name: &str, sample: Sample<serde_json::Value>) -> Result<()> {
+        let sample = Sample {
+            timestamp: sample.timestamp,
+            value: serde_json::from_value(sample.value)?,
+        };
+        self.0.store_sample(name, sample).await;
+        Ok(())
+    }
+
+    async fn history(
+        &self,
+        _from: DateTime<Utc>,
+        _to: DateTime<Utc>,
+        _step: Option<i64>,
+    ) -> Option<Result<Vec<Sample<RollupMetrics>>>> {
+        None
+    }
+
+    async fn run_maintenance(&self) -> Result<()> {
+        self.0.run_maintenance().await
+    }
+}
+
+#[async_trait]
+impl<T> RegisteredStore for RollupStore<T>
+where
+    T: Rollup + Clone + Debug + Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    fn as_any(&self) -> &dyn Any {
+        self.0.as_any()
+    }
+
+    async fn latest_json(&self) -> Option<serde_json::Value> {
+        self.0.latest_json().await
+    }
+
+    async fn store_json(&self, name: &str, sample: Sample<serde_json::Value>) -> Result<()> {
+        self.0.store_json(name, sample).await
+    }
+
+    async fn history(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        step: Option<i64>,
+    ) -> Option<Result<Vec<Sample<RollupMetrics>>>> {
+        Some(resource_history(&self.0 .0, from, to, step).await)
+    }
+
+    async fn run_maintenance(&self) -> Result<()> {
+        self.0.run_maintenance().await
+    }
+}
+
+async fn resource_history<T>(
+    store: &MetricStore<T>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    step: Option<i64>,
+) -> Result<Vec<Sample<RollupMetrics>>>
+where
+    T: Rollup + Clone + Debug + Serialize + DeserializeOwned,
+{
+    if let Some(resolution) = step.and_then(Resolution::from_seconds) {
+        return Ok(store.rollups(resolution, from, to).await);
+    }
+    let samples = store
+        .history(from, to)
+        .await?
+        .into_iter()
+        .map(|sample| Sample {
+            timestamp: sample.timestamp,
+            value: sample.value.metrics(),
+        })
+        .collect();
+    Ok(downsample(samples, step))
+}
+
+// Starts the collector in the set after the given delay and returns its task id, again on every restart
+type Spawn = Box<dyn Fn(&mut JoinSet<()>, Duration) -> tokio::task::Id + Send>;
+type Observer<T> = Option<Arc<dyn CollectorObserver<T>>>;
+
+// A collector task the watcher restarts when it stops
+struct Running {
+    name: &'static str,
+    spawn: Spawn,
+    // Consecutive restarts, the backoff grows with them
+    restarts: u32,
+    started: Instant,
+}
+
+async fn watch(mut tasks: JoinSet<()>, mut running: HashMap<tokio::task::Id, Running>) {
+    while let Some(result) = tasks.join_next_with_id().await {
+        let (id, error) = match result {
+            Ok((id, ())) => (id, None),
+            Err(e) => (e.id(), Some(e)),
+        };
+        let Some(mut collector) = running.remove(&id) else {
+            continue;
+        };
+        // A run that outlasted the longest backoff was healthy, start over from the shortest
+        if collector.started.elapsed() > RESTART_BACKOFF_MAX {
+            collector.restarts = 0;
+        }
+        let backoff = restart_backoff(collector.restarts);
+        tracing::error!(
+            "{} collector stopped, restarting in {:?}: {:?}",
+            collector.name,
+            backoff,
+            error
+        );
+        counter!("collector.stopped", "collector" => collector.name).increment(1);
+        collector.restarts += 1;
+        collector.started = Instant::now() + backoff;
+        let id = (collector.spawn)(&mut tasks, backoff);
+        running.insert(id, collector);
+    }
+}
+
+fn restart_backoff(restarts: u32) -> Duration {
+    RESTART_BACKOFF_MIN
+        .saturating_mul(2u32.saturating_pow(restarts))
+        .min(RESTART_BACKOFF_MAX)
+}
+
+/**
+ Every collector the monitor runs along with where its samples go.
+
+ Registering is all a new collector needs, its latest sample shows up in the snapshot under its
+ name, replays feed it and, when its output rolls up, `/history/<name>` serves it. Collectors only
+ start ticking once `spawn_all` is called.
+**/
+#[derive(Default)]
+pub struct CollectorRegistry {
+    stores: HashMap<&'static str, Box<dyn RegisteredStore>>,
+    pending: Mutex<Vec<(&'static str, Spawn)>>,
+}
+
+impl CollectorRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `observer` sees every sample before it is stored, for anything derived from it
+    pub fn register<C: Collector>(
+        self,
+        collector: C,
+        store: MetricStore<C::Output>,
+        observer: Observer<C::Output>,
+    ) -> Self {
+        let store = Arc::new(store);
+        let entry = Box::new(Store(Arc::clone(&store)));
+        self.add(collector, store, entry, observer)
+    }
+
+    /// Registers a collector whose samples also roll up for long range history
+    pub fn register_with_rollups<C>(
+        self,
+        collector: C,
+        store: MetricStore<C::Output>,
+        observer: Observer<C::Output>,
+    ) -> Self
+    where
+        C: Collector,
+        C::Output: Rollup,
+    {
+        let store = Arc::new(store.with_rollups());
+        let entry = Box::new(RollupStore(Store(Arc::clone(&store))));
+        self.add(collector, store, entry, observer)
+    }
+
+    fn add<C: Collector>(
+        mut self,
+        collector: C,
+        store: Arc<MetricStore<C::Output>>,
+        entry: Box<dyn RegisteredStore>,
+        observer: Observer<C::Output>,
+    ) -> Self {
+        let name = collector.name();
+        if self.stores.insert(name, entry).is_some() {
+            tracing::warn!("Collector {} registered twice, only the last one is kept", name);
+        }
+        let interval = collector.interval();
+        let timeout = collector.timeout();
+        let runner = Arc::new(AsyncMutex::new(BlockingCollector::new(name, collector, timeout)));
+        let spawn: Spawn = Box::new(move |tasks, delay| {
+            let runner = Arc::clone(&runner);
+            let store = Arc::clone(&store);
+            let observer = observer.clone();
+            // `supervise` never returns, a collector task only ends by panicking or being aborted
+            tasks
+                .spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    supervise(runner, interval, store, observer).await
+                })
+                .id()
+        });
+        self.pending
+            .get_mut()
+            .expect("Collector registry lock poisoned")
+            .push((name, spawn));
+        self
+    }
+
+    /**
+     Starts every registered collector that isn't running yet.
+
+     The returned task watches them, a collector that stops (its observer or store panicked) is
+     logged, counted and restarted with its state after a backoff. Aborting it stops every
+     collector it started.
+    **/
+    pub fn spawn_all(&self) -> JoinHandle<()> {
+        let pending = std::mem::take(&mut *self.pending.lock().expect("Collector registry lock poisoned"));
+        let mut tasks = JoinSet::new();
+        let running = pending
+            .into_iter()
+            .map(|(name, spawn)| {
+                let id = spawn(&mut tasks, Duration::ZERO);
+                let running = Running {
+                    name,
+                    spawn,
+                    restarts: 0,
+                    started: Instant::now(),
+                };
+                (id, running)
+            })
+            .collect();
+        tokio::spawn(watch(tasks, running))
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut names: Vec<&'static str> = self.stores.keys().copied().collect();
+        names.sort();
+        names
+    }
+
+    /// Typed access to a collector's store, None for unknown names or the wrong output type
+    pub fn store<T: 'static>(&self, name: &str) -> Option<Arc<MetricStore<T>>> {
+        self.stores
+            .get(name)?
+            .as_any()
+            .downcast_ref::<Arc<MetricStore<T>>>()
+            .cloned()
+    }
+
+    pub async fn latest<T: Clone + Debug + Serialize + DeserializeOwned + 'static>(
+        &self,
+        name: &str,
+    ) -> Option<T> {
+        self.store::<T>(name)?.get_recent(1).await.pop()
+    }
+
+    /// Latest sample of every collector not in `skip`, keyed by name
+    pub async fn latest_json(&self, skip: &[&str]) -> BTreeMap<String, serde_json::Value> {
+        let mut latest = BTreeMap::new();
+        for (name, store) in &self.stores {
+            if skip.contains(name) {
+                continue;
+            }
+            if let Some(value) = store.latest_json().await {
+                latest.insert(name.to_string(), value);
+            }
+        }
+        latest
+    }
+
+    /// Stores a sample that didn't come from the collector itself, e.g. a replayed one
+    pub async fn store_json(&self, name: &str, sample: Sample<serde_json::Value>) -> Result<()> {
+        match self.stores.get(name) {
+            Some(store) => store.store_json(name, sample).await,
+            None => Err(anyhow::anyhow!("No collector named {}", name)),
+        }
+    }
+
+    /// None when there is no such collector or it keeps no history
+    pub async fn history(
+        &self,
+        name: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        step: Option<i64>,
+    ) -> Option<Result<Vec<Sample<RollupMetrics>>>> {
+        self.stores.get(name)?.history(from, to, step).await
+    }
+
+    pub async fn run_maintenance(&self) -> Result<()> {
+        for store in self.stores.values() {
+            store.run_maintenance().await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_blocking_collector_skips_while_timed_out() {
+        let mut collector = BlockingCollector::new("test", 0u32, Duration::from_millis(50));
+        let count = |state: &mut u32| {
+            *state += 1;
+            *state
+        };
+        assert_eq!(collector.collect(count).await, Some(1));
+
+        let slow = |state: &mut u32| {
+            std::thread::sleep(Duration::from_millis(300));
+            *state += 1;
+            *state
+        };
+        assert_eq!(collector.collect(slow).await, None);
+        assert_eq!(collector.collect(count).await, None, "Slow run still holds the state");
+
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(collector.collect(count).await, Some(3), "State came back from the slow run");
+
+        // Printing the panic can take longer than the short timeout above
+        let mut collector = BlockingCollector::new("test", 3u32, Duration::from_secs(30));
+        let panics = |_: &mut u32| -> u32 { panic!("collector bug") };
+        assert_eq!(collector.collect(panics).await, None);
+        assert_eq!(collector.collect(count).await, Some(4), "State survives a panic");
+    }
+
+    struct Counter(u64);
+    impl Collector for Counter {
+        type Output = u64;
+        fn name(&self) -> &'static str {
+            "counter"
+        }
+        fn interval(&self) -> Duration {
+            Duration::from_millis(10)
+        }
+        fn collect(&mut self) -> Result<u64> {
+            self.0 += 1;
+            Ok(self.0)
+        }
+    }
+
+    struct Seen(std::sync::atomic::AtomicU64);
+    #[async_trait]
+    impl CollectorObserver<u64> for Seen {
+        async fn observe(&self, value: &u64) {
+            self.0.store(*value, std::sync::atomic::Ordering::Relaxed);
+            assert_ne!(*value, 3, "observer bug");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registry_runs_and_exposes_collectors() {
+        let registry =
+            CollectorRegistry::new().register(Counter(0), MetricStore::new(10, 60), None);
+        assert_eq!(registry.names(), vec!["counter"]);
+        assert!(registry.latest_json(&[]).await.is_empty());
+
+        let watcher = registry.spawn_all();
+        tokio::time::timeout(Duration::from_secs(1), registry.spawn_all())
+            .await
+            .expect("Collectors only start once, nothing left to watch")
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        watcher.abort();
+
+        assert!(registry.latest::<u64>("counter").await.unwrap() > 0);
+        assert!(registry.latest_json(&[]).await.contains_key("counter"));
+        assert!(registry.latest_json(&["counter"]).await.is_empty());
+        assert!(registry.store::<String>("counter").is_none(), "Wrong type");
+        assert!(registry.history("counter", Utc::now(), Utc::now(), None).await.is_none());
+
+        registry
+            .store_json("counter", Sample::now(serde_json::json!(100)))
+            .await
+            .unwrap();
+        assert_eq!(registry.latest::<u64>("counter").await, Some(100));
+        assert!(registry
+            .store_json("missing", Sample::now(serde_json::json!(1)))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_registry_observes_and_watches_collectors() {
+        let seen = Arc::new(Seen(0.into()));
+        let observer: Arc<dyn CollectorObserver<u64>> = seen.clone();
+        let registry =
+            CollectorRegistry::new().register(Counter(0), MetricStore::new(10, 60), Some(observer));
+
+        // The observer panics on the third sample, which takes the collector down with it
+        let watcher = registry.spawn_all();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(seen.0.load(std::sync::atomic::Ordering::Relaxed), 3);
+        assert_eq!(registry.latest::<u64>("counter").await, Some(2));
+
+        // Restarted after the backoff, counting on from where it stopped
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while registry.latest::<u64>("counter").await < Some(5) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Collector should be restarted");
+        assert!(!watcher.is_finished());
+        watcher.abort();
+        assert!(seen.0.load(std::sync::atomic::Ordering::Relaxed) >= 5);
+        assert_eq!(restart_backoff(0), RESTART_BACKOFF_MIN);
+        assert_eq!(restart_backoff(1), RESTART_BACKOFF_MIN * 2);
+        assert_eq!(restart_backoff(40), RESTART_BACKOFF_MAX);
+    }
+}
@@ -1,8 +1,13 @@
 
 #[derive(serde::Deserialize,Clone,Default)]
 pub struct Settings {
-    pub monitor: MonitorSettings
-
+    pub monitor: MonitorSettings,
+    #[serde(default)]
+    pub storage: StorageSettings,
+    #[serde(default)]
+    pub filters: FilterSettings,
+    #[serde(default)]
+    pub lineage: LineageSettings,
 }
 
 
@@ -11,7 +16,146 @@ pub struct MonitorSettings {
     pub ollama_url: String,
     pub model: String,
     pub context_size: u32,
-    pub offline: bool
+    pub offline: bool,
+    // Use the netlink proc connector for process events, falls back to polling without root
+    #[serde(default)]
+    pub proc_connector: bool
+}
+
+#[derive(serde::Deserialize,Clone)]
+#[serde(default)]
+pub struct StorageSettings {
+    pub enabled: bool,
+    // Root directory, each resource gets its own sub directory of segment files
+    pub path: String,
+    pub retention_hours: u64,
+    // A segment is sealed once it hits either of these limits
+    pub segment_max_bytes: u64,
+    pub segment_duration_minutes: u64,
+    // How often retention and compaction run
+    pub maintenance_interval_secs: u64,
+}
+
+impl Default for StorageSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: "data".into(),
+            retention_hours: 168,
+            segment_max_bytes: 8 * 1024 * 1024,
+            segment_duration_minutes: 60,
+            maintenance_interval_secs: 300,
+        }
+    }
+}
+
+/**
+ Which processes the scanner keeps, applied before tagging so excluded processes never reach
+ attributes, history, events or Ollama.
+
+ A process is dropped when it matches any `exclude` rule, or when `include` has rules and it
+ matches none of them. Names, commands and cgroups are regexes, the command is matched against the
+ space joined cmdline and cgroups against the cgroup path. Users are names or uids.
+**/
+#[derive(serde::Deserialize,Clone)]
+#[serde(default)]
+pub struct FilterSettings {
+    // sysinfo status names, e.g. Dead, Idle, Zombie
+    pub ignore_statuses: Vec<String>,
+    pub ignore_kernel_threads: bool,
+    pub include: ProcessMatchSettings,
+    pub exclude: ProcessMatchSettings,
+}
+
+impl Default for FilterSettings {
+    fn default() -> Self {
+        Self {
+            ignore_statuses: vec!["Dead".into(), "Idle".into()],
+            ignore_kernel_threads: false,
+            include: ProcessMatchSettings::default(),
+            exclude: ProcessMatchSettings::default(),
+        }
+    }
+}
+
+#[derive(serde::Deserialize,Clone,Default)]
+#[serde(default)]
+pub struct ProcessMatchSettings {
+    pub names: Vec<String>,
+    pub commands: Vec<String>,
+    pub users: Vec<String>,
+    pub cgroups: Vec<String>,
+}
+
+/**
+ Parent chains worth flagging, checked against every scanned process.
+
+ A rule matches when the process name matches one of `processes` and one of its closest
+ `max_depth` ancestors matches one of `ancestors`, both are regexes against process names. So a
+ depth of 1 only looks at the direct parent, the default of 3 also catches something like
+ cron -> sh -> curl.
+**/
+#[derive(serde::Deserialize,Clone)]
+#[serde(default)]
+pub struct LineageSettings {
+    pub rules: Vec<LineageRuleSettings>,
+}
+
+impl Default for LineageSettings {
+    fn default() -> Self {
+        let rule = |name: &str, ancestors: &str, processes: &str| LineageRuleSettings {
+            name: name.into(),
+            ancestors: vec![ancestors.into()],
+            processes: vec![processes.into()],
+            ..Default::default()
+        };
+        Self {
+            rules: vec![
+                rule(
+                    "service_shell",
+                    "^(nginx|apache2|httpd|lighttpd|caddy|php-fpm.*|java|postgres|mysqld|mariadbd|mongod|redis-server)$",
+                    "^(ba|da|z|k|c|tc|fi)?sh$",
+                ),
+                // Web apps fetch things with curl all the time, raw socket tools are a reverse shell
+                rule(
+                    "service_network_tool",
+                    "^(nginx|apache2|httpd|lighttpd|caddy|php-fpm.*|java|postgres|mysqld|mariadbd|mongod|redis-server)$",
+                    "^(nc|ncat|netcat|socat|telnet)$",
+                ),
+                rule(
+                    "document_interpreter",
+                    "^(soffice.*|oosplash|evince|okular|thunderbird|firefox|chrome|chromium.*|msedge)$",
+                    "^(python[0-9.]*|perl|ruby|php[0-9.]*|lua[0-9.]*|node|wscript|powershell|pwsh)$",
+                ),
+                rule(
+                    "scheduled_network_tool",
+                    "^(cron|crond|anacron|atd)$",
+                    "^(nc|ncat|netcat|socat|curl|wget|telnet|ftp|tftp)$",
+                ),
+            ],
+        }
+    }
+}
+
+#[derive(serde::Deserialize,Clone)]
+#[serde(default)]
+pub struct LineageRuleSettings {
+    // Shows up in the tag so it is clear which rule fired
+    pub name: String,
+    pub ancestors: Vec<String>,
+    pub processes: Vec<String>,
+    pub max_depth: usize,
+}
+
+impl Default for LineageRuleSettings {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            ancestors: vec![],
+            processes: vec![],
+            max_depth: 3,
+        }
+    }
 }
 
 pub fn get_configuration() -> Result<Settings, config::ConfigError> {
@@ -0,0 +1,636 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, mpsc, Mutex};
+
+use crate::{
+    cache::RingBuffer,
+    os_tooling::{
+        command_line::CommandLineAttribute,
+        filter::ProcessFilter,
+        privileges::UserTable,
+        process::{
+            unique_processes, OsProcessGroup, OsProcessInformation, ProcessAttribute,
+            ProcessIdentity,
+        },
+        proc_connector::{ConnectorEvent, ProcConnector},
+        procfs,
+    },
+    storage::Sample,
+};
+
+// Events kept in memory, older ones are dropped first
+const EVENT_LOG_CAPACITY: usize = 10_000;
+// Subscribers that fall further behind than this start missing events
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+const DEFAULT_EVENT_LIMIT: usize = 500;
+// Short lived processes kept for the snapshot
+const TRANSIENT_CAPACITY: usize = 200;
+const CONNECTOR_CHANNEL_CAPACITY: usize = 4096;
+const CONNECTOR_BUFFER_BYTES: usize = 8192;
+// Exits are lost when the connector overruns its buffer, tracked processes that are gone get dropped
+const LIVE_PRUNE_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum ProcessEvent {
+    ProcessStarted {
+        identity: ProcessIdentity,
+        name: String,
+        parent_pid: Option<u32>,
+        exe: Option<String>,
+        #[serde(default)]
+        command: Vec<String>,
+    },
+    ProcessExited {
+        identity: ProcessIdentity,
+        name: String,
+    },
+    // Same pid and start time but a different program, i.e. the process called exec
+    ProcessExecChanged {
+        identity: ProcessIdentity,
+        name: String,
+        previous_name: String,
+        exe: Option<String>,
+        previous_exe: Option<String>,
+        #[serde(default)]
+        command: Vec<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessEventKind {
+    Started,
+    Exited,
+    ExecChanged,
+}
+
+impl ProcessEvent {
+    pub fn identity(&self) -> &ProcessIdentity {
+        match self {
+            ProcessEvent::ProcessStarted { identity, .. }
+            | ProcessEvent::ProcessExited { identity, .. }
+            | ProcessEvent::ProcessExecChanged { identity, .. } => identity,
+        }
+    }
+
+    pub fn kind(&self) -> ProcessEventKind {
+        match self {
+            ProcessEvent::ProcessStarted { .. } => ProcessEventKind::Started,
+            ProcessEvent::ProcessExited { .. } => ProcessEventKind::Exited,
+            ProcessEvent::ProcessExecChanged { .. } => ProcessEventKind::ExecChanged,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct EventQuery {
+    // Defaults to everything still in the log
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub kind: Option<ProcessEventKind>,
+    pub pid: Option<u32>,
+    // Newest events win when there are more than this, defaults to 500
+    pub limit: Option<usize>,
+}
+
+// What a process was running the last time we looked
+#[derive(Debug, Clone)]
+struct ProcessImage {
+    name: String,
+    exe: Option<String>,
+    exe_hash: Option<String>,
+    command: Vec<String>,
+}
+
+impl From<&OsProcessInformation> for ProcessImage {
+    fn from(process: &OsProcessInformation) -> Self {
+        Self {
+            name: process.name.clone(),
+            exe: process.exe.as_ref().map(|exe| exe.path.clone()),
+            exe_hash: process.identity.exe_hash.clone(),
+            command: process.command.clone(),
+        }
+    }
+}
+
+impl ProcessImage {
+    fn exec_changed(&self, current: &ProcessImage) -> bool {
+        match (&self.exe, &current.exe) {
+            (Some(previous), Some(exe)) => {
+                previous != exe
+                    || matches!((&self.exe_hash, &current.exe_hash), (Some(a), Some(b)) if a != b)
+            }
+            // Without the exe (other users' processes) the name is all we have
+            _ => self.name != current.name,
+        }
+    }
+}
+
+/**
+ Turns consecutive scans into lifecycle events.
+
+ The first scan is only a baseline, everything already running then is not reported as started.
+ A process is matched across scans by identity, so a recycled pid shows up as an exit and a start.
+**/
+#[derive(Default)]
+pub struct ProcessDiffer {
+    previous: Option<HashMap<ProcessIdentity, ProcessImage>>,
+}
+
+impl ProcessDiffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn diff(&mut self, groups: &[OsProcessGroup]) -> Vec<ProcessEvent> {
+        // Kernel threads never exec, they churn and flap in and out of the idle filter, so they'd only be noise
+        let processes: Vec<&OsProcessInformation> = unique_processes(groups)
+            .into_iter()
+            .filter(|process| !process.is_kernel_thread())
+            .collect();
+        let current: HashMap<ProcessIdentity, ProcessImage> = processes
+            .iter()
+            .map(|process| (process.identity.clone(), ProcessImage::from(*process)))
+            .collect();
+
+        // First scan is the baseline
+        let events = match &self.previous {
+            Some(previous) => changes(previous, &current, &processes),
+            None => Vec::new(),
+        };
+        self.previous = Some(current);
+        events
+    }
+}
+
+fn changes(
+    previous: &HashMap<ProcessIdentity, ProcessImage>,
+    current: &HashMap<ProcessIdentity, ProcessImage>,
+    processes: &[&OsProcessInformation],
+) -> Vec<ProcessEvent> {
+    let mut events = Vec::new();
+    for process in processes {
+        let image = &current[&process.identity];
+        match previous.get(&process.identity) {
+            None => events.push(ProcessEvent::ProcessStarted {
+                identity: process.identity.clone(),
+                name: image.name.clone(),
+                parent_pid: process.parent_pid,
+                exe: image.exe.clone(),
+                command: image.command.clone(),
+            }),
+            Some(before) if before.exec_changed(image) => {
+                events.push(ProcessEvent::ProcessExecChanged {
+                    identity: process.identity.clone(),
+                    name: image.name.clone(),
+                    previous_name: before.name.clone(),
+                    exe: image.exe.clone(),
+                    previous_exe: before.exe.clone(),
+                    command: image.command.clone(),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    let mut exited: Vec<(&ProcessIdentity, &ProcessImage)> = previous
+        .iter()
+        .filter(|(identity, _)| !current.contains_key(*identity))
+        .collect();
+    exited.sort_by_key(|(identity, _)| identity.pid);
+    events.extend(
+        exited
+            .into_iter()
+            .map(|(identity, image)| ProcessEvent::ProcessExited {
+                identity: identity.clone(),
+                name: image.name.clone(),
+            }),
+    );
+    events
+}
+
+/**
+ Bounded log of process lifecycle events.
+
+ Every scan is fed through `observe`, the resulting events are kept for queries and broadcast to
+ anything that called `subscribe`, which is how analysis and alerting can react to new processes
+ instead of polling snapshots.
+
+ While the proc connector is running it is the only source of events. Scans keep the differ's
+ baseline current so polling picks up cleanly if the connector goes away.
+**/
+pub struct EventLog {
+    events: Mutex<RingBuffer<ProcessEvent>>,
+    differ: Mutex<ProcessDiffer>,
+    sender: broadcast::Sender<Sample<ProcessEvent>>,
+    transient: Mutex<RingBuffer<OsProcessInformation>>,
+    connector_live: AtomicBool,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new(EVENT_LOG_CAPACITY)
+    }
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        Self {
+            events: Mutex::new(RingBuffer::new(capacity)),
+            differ: Mutex::new(ProcessDiffer::new()),
+            sender,
+            transient: Mutex::new(RingBuffer::new(TRANSIENT_CAPACITY)),
+            connector_live: AtomicBool::new(false),
+        }
+    }
+
+    /// Diffs a scan against the previous one and records the events, returns how many there were
+    pub async fn observe(&self, groups: &[OsProcessGroup]) -> usize {
+        self.observe_at(groups, Utc::now()).await
+    }
+
+    /// `observe` for a scan taken at `timestamp`, e.g. a replayed one
+    pub async fn observe_at(&self, groups: &[OsProcessGroup], timestamp: DateTime<Utc>) -> usize {
+        let events = self.differ.lock().await.diff(groups);
+        if self.connector_live.load(Ordering::Relaxed) {
+            return 0;
+        }
+        let count = events.len();
+        for event in events {
+            self.record_at(event, timestamp).await;
+        }
+        count
+    }
+
+    pub async fn record(&self, event: ProcessEvent) {
+        self.record_at(event, Utc::now()).await;
+    }
+
+    async fn record_at(&self, event: ProcessEvent, timestamp: DateTime<Utc>) {
+        let sample = Sample {
+            timestamp,
+            value: event,
+        };
+        tracing::debug!("Process event {:?}", sample.value);
+        self.events.lock().await.push(sample.clone());
+        // Nobody listening is fine
+        let _ = self.sender.send(sample);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Sample<ProcessEvent>> {
+        self.sender.subscribe()
+    }
+
+    pub async fn record_transient(&self, process: OsProcessInformation) {
+        self.transient.lock().await.push(Sample::now(process));
+    }
+
+    /// Processes that came and went between scans, oldest first
+    pub async fn transient_processes(&self) -> Vec<OsProcessInformation> {
+        self.transient
+            .lock()
+            .await
+            .iter()
+            .map(|sample| sample.value.clone())
+            .collect()
+    }
+
+    fn set_connector_live(&self, live: bool) {
+        self.connector_live.store(live, Ordering::Relaxed);
+    }
+
+    /// Matching events oldest first, capped to the newest `limit`
+    pub async fn query(&self, query: &EventQuery) -> Vec<Sample<ProcessEvent>> {
+        let events = self.events.lock().await;
+        let from = query.from.unwrap_or(DateTime::<Utc>::MIN_UTC);
+        let to = query.to.unwrap_or(DateTime::<Utc>::MAX_UTC);
+        let limit = query.limit.unwrap_or(DEFAULT_EVENT_LIMIT);
+        let mut matching: Vec<Sample<ProcessEvent>> = events
+            .range(from, to)
+            .rev()
+            .filter(|sample| query.kind.is_none_or(|kind| sample.value.kind() == kind))
+            .filter(|sample| query.pid.is_none_or(|pid| sample.value.identity().pid == pid))
+            .take(limit)
+            .cloned()
+            .collect();
+        matching.reverse();
+        matching
+    }
+}
+
+// Result of one connector notification
+#[derive(Debug, Default)]
+pub struct ConnectorUpdate {
+    pub event: Option<ProcessEvent>,
+    // Set when a process exits before it could have shown up in a scan
+    pub transient: Option<OsProcessInformation>,
+}
+
+/**
+ Turns proc connector notifications into the same events and process model the scanner produces.
+
+ Processes are read from /proc the moment they fork or exec, so droppers and one-shot commands keep
+ their cmdline and parent even though they are gone long before the next scan. Processes that were
+ already running when the connector started are only picked up once they exec or exit.
+**/
+pub struct ConnectorTracker {
+    // Start is unknown for processes that were already running when the connector started
+    live: HashMap<u32, (OsProcessInformation, Option<Instant>)>,
+    transient_lifetime: Duration,
+    users: UserTable,
+    filter: ProcessFilter,
+    // Short lived processes are exactly where a `curl | sh` shows up, so they get checked too
+    command_line: CommandLineAttribute,
+    last_prune: Instant,
+}
+
+impl ConnectorTracker {
+    pub fn new(transient_lifetime: Duration) -> Self {
+        Self {
+            live: HashMap::new(),
+            transient_lifetime,
+            users: UserTable::load(),
+            filter: ProcessFilter::default(),
+            command_line: CommandLineAttribute::new(),
+            last_prune: Instant::now(),
+        }
+    }
+
+    pub fn with_filter(mut self, filter: ProcessFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    // None once the process is gone or filtered out
+    fn read(&self, pid: u32) -> Option<OsProcessInformation> {
+        let mut process = OsProcessInformation::from_procfs(pid).ok()?;
+        if let Some(privileges) = &mut process.privileges {
+            privileges.resolve_names(&self.users);
+        }
+        if !self.filter.allows(&process) {
+            return None;
+        }
+        self.command_line.tag(&mut process);
+        Some(process)
+    }
+
+    pub fn handle(&mut self, event: ConnectorEvent) -> ConnectorUpdate {
+        match event {
+            ConnectorEvent::Fork { child_pid, .. } => {
+                let Some(process) = self.read(child_pid) else {
+                    return ConnectorUpdate::default();
+                };
+                self.started(process)
+            }
+            ConnectorEvent::Exec { pid } => {
+                let Some(process) = self.read(pid) else {
+                    return ConnectorUpdate::default();
+                };
+                if process.is_kernel_thread() {
+                    return ConnectorUpdate::default();
+                }
+                let (before, started) = match self.live.remove(&pid) {
+                    Some((before, started)) => (Some(before), started),
+                    None => (None, None),
+                };
+                let event = ProcessEvent::ProcessExecChanged {
+                    identity: process.identity.clone(),
+                    name: process.name.clone(),
+                    previous_name: before.as_ref().map(|p| p.name.clone()).unwrap_or_default(),
+                    exe: process.exe.as_ref().map(|exe| exe.path.clone()),
+                    previous_exe: before.and_then(|p| p.exe).map(|exe| exe.path),
+                    command: process.command.clone(),
+                };
+                self.live.insert(pid, (process, started));
+                ConnectorUpdate {
+                    event: Some(event),
+                    transient: None,
+                }
+            }
+            ConnectorEvent::Exit { pid, .. } => {
+                // Exit fires before the process is reaped so /proc is usually still readable
+                let (process, started) = match self.live.remove(&pid) {
+                    Some(entry) => entry,
+                    None => match self.read(pid) {
+                        Some(process) if !process.is_kernel_thread() => (process, None),
+                        _ => return ConnectorUpdate::default(),
+                    },
+                };
+                let event = ProcessEvent::ProcessExited {
+                    identity: process.identity.clone(),
+                    name: process.name.clone(),
+                };
+                let transient = started
+                    .is_some_and(|started| started.elapsed() < self.transient_lifetime)
+                    .then_some(process);
+                ConnectorUpdate {
+                    event: Some(event),
+                    transient,
+                }
+            }
+        }
+    }
+
+    /// Forgets processes whose exit never arrived, at most once per `LIVE_PRUNE_INTERVAL`
+    pub fn prune(&mut self) {
+        if self.last_prune.elapsed() < LIVE_PRUNE_INTERVAL {
+            return;
+        }
+        self.last_prune = Instant::now();
+        // A different start time means the pid was reused after the exit we missed
+        self.live.retain(|pid, (process, _)| {
+            procfs::read_start_time(*pid).is_ok_and(|start| start == process.identity.start_time)
+        });
+    }
+
+    fn started(&mut self, process: OsProcessInformation) -> ConnectorUpdate {
+        if process.is_kernel_thread() {
+            return ConnectorUpdate::default();
+        }
+        let event = ProcessEvent::ProcessStarted {
+            identity: process.identity.clone(),
+            name: process.name.clone(),
+            parent_pid: process.parent_pid,
+            exe: process.exe.as_ref().map(|exe| exe.path.clone()),
+            command: process.command.clone(),
+        };
+        self.live.insert(process.pid, (process, Some(Instant::now())));
+        ConnectorUpdate {
+            event: Some(event),
+            transient: None,
+        }
+    }
+}
+
+/**
+ Feeds proc connector notifications into `log` until the connector fails.
+
+ Returns straight away when the connector can't be opened (not root, no CAP_NET_ADMIN, not Linux),
+ in both cases the scan diffs take over as the event source.
+**/
+pub async fn run_proc_connector(
+    log: Arc<EventLog>,
+    transient_lifetime: Duration,
+    filter: ProcessFilter,
+) {
+    let connector = match ProcConnector::open() {
+        Ok(connector) => connector,
+        Err(e) => {
+            tracing::warn!("Process connector unavailable, falling back to polling: {}", e);
+            return;
+        }
+    };
+    tracing::info!("Listening for process events on the proc connector");
+
+    let (sender, mut receiver) = mpsc::channel(CONNECTOR_CHANNEL_CAPACITY);
+    // recv blocks, keep it off the runtime. /proc is read right here too since short lived
+    // processes are often gone by the time anything queued behind a channel gets to them
+    std::thread::spawn(move || {
+        let mut tracker = ConnectorTracker::new(transient_lifetime).with_filter(filter);
+        let mut buf = vec![0u8; CONNECTOR_BUFFER_BYTES];
+        loop {
+            match connector.recv(&mut buf) {
+                Ok(events) => {
+                    for event in events {
+                        if sender.blocking_send(tracker.handle(event)).is_err() {
+                            return;
+                        }
+                    }
+                    tracker.prune();
+                }
+                Err(e) => {
+                    tracing::error!("Process connector failed: {}", e);
+                    return;
+                }
+            }
+        }
+    });
+
+    log.set_connector_live(true);
+    while let Some(update) = receiver.recv().await {
+        if let Some(event) = update.event {
+            log.record(event).await;
+        }
+        if let Some(process) = update.transient {
+            log.record_transient(process).await;
+        }
+    }
+    log.set_connector_live(false);
+    tracing::warn!("Process connector stopped, falling back to polling");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::os_tooling::executable::ExecutableInfo;
+
+    fn process(pid: u32, start_time: u64, name: &str, exe: &str) -> OsProcessInformation {
+        let mut process = OsProcessInformation::default();
+        process.pid = pid;
+        process.identity = ProcessIdentity::new(pid, start_time);
+        process.name = name.to_string();
+        process.exe = Some(ExecutableInfo {
+            path: exe.to_string(),
+            ..Default::default()
+        });
+        process
+    }
+
+    fn group(parent: OsProcessInformation, children: Vec<OsProcessInformation>) -> OsProcessGroup {
+        OsProcessGroup {
+            parent_process: parent,
+            children,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_event_log_diffs_scans() {
+        let log = EventLog::new(100);
+        let mut events = log.subscribe();
+
+        let baseline = vec![group(
+            process(1, 1, "systemd", "/usr/lib/systemd/systemd"),
+            vec![process(100, 50, "bash", "/usr/bin/bash")],
+        )];
+        assert_eq!(log.observe(&baseline).await, 0);
+
+        // bash execs into curl, a new sshd shows up, and pid 100 later gets recycled
+        let next = vec![group(
+            process(1, 1, "systemd", "/usr/lib/systemd/systemd"),
+            vec![
+                process(100, 50, "curl", "/usr/bin/curl"),
+                process(200, 80, "sshd", "/usr/sbin/sshd"),
+            ],
+        )];
+        assert_eq!(log.observe(&next).await, 2);
+        let recycled = vec![group(
+            process(1, 1, "systemd", "/usr/lib/systemd/systemd"),
+            vec![
+                process(100, 90, "nc", "/usr/bin/nc"),
+                process(200, 80, "sshd", "/usr/sbin/sshd"),
+            ],
+        )];
+        assert_eq!(log.observe(&recycled).await, 2);
+
+        let first = events.recv().await.unwrap();
+        assert_eq!(first.value.kind(), ProcessEventKind::ExecChanged);
+
+        let for_pid = log
+            .query(&EventQuery {
+                pid: Some(100),
+                ..Default::default()
+            })
+            .await;
+        let kinds: Vec<ProcessEventKind> = for_pid.iter().map(|e| e.value.kind()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ProcessEventKind::ExecChanged,
+                ProcessEventKind::Started,
+                ProcessEventKind::Exited
+            ]
+        );
+
+        let started = log
+            .query(&EventQuery {
+                kind: Some(ProcessEventKind::Started),
+                limit: Some(1),
+                ..Default::default()
+            })
+            .await;
+        assert_eq!(started.len(), 1);
+        assert_eq!(started[0].value.identity(), &ProcessIdentity::new(100, 90));
+    }
+
+    #[test]
+    fn test_connector_tracker_prunes_lost_exits() {
+        let own_pid = std::process::id();
+        let own = process(own_pid, procfs::read_start_time(own_pid).unwrap(), "odin", "/odin");
+        let recycled = process(own_pid, 1, "odin", "/odin");
+        let mut tracker = ConnectorTracker::new(Duration::from_secs(5));
+        tracker.live.insert(own_pid, (own, None));
+        // Pids never go this high, its exit was lost
+        tracker.live.insert(u32::MAX, (process(u32::MAX, 1, "gone", "/gone"), None));
+
+        tracker.prune();
+        assert_eq!(tracker.live.len(), 2, "Pruning waits for the interval");
+        tracker.last_prune -= LIVE_PRUNE_INTERVAL;
+        tracker.prune();
+        assert_eq!(tracker.live.keys().collect::<Vec<_>>(), vec![&own_pid]);
+
+        tracker.live.insert(own_pid, (recycled, None));
+        tracker.last_prune -= LIVE_PRUNE_INTERVAL;
+        tracker.prune();
+        assert!(tracker.live.is_empty(), "A reused pid is a different process");
+    }
+}
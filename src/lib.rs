@@ -2,7 +2,10 @@ pub mod ollama;
 pub mod telemetry;
 pub mod os_tooling;
 pub mod cache;
+pub mod storage;
 pub mod configuration;
+pub mod collector;
 pub mod monitor;
+pub mod events;
 pub mod utils;
 pub mod web;
\ No newline at end of file
@@ -1,10 +1,9 @@
 use clap::Parser;
 use odin::{
-    cache::Cache, configuration::get_configuration, monitor::SystemMonitor, ollama::OllamaClient, os_tooling::process::OsProcessGroup, telemetry::{get_subscriber, init_subscriber}, web::app::start_server
+    configuration::get_configuration, monitor::SystemMonitor, ollama::OllamaClient, telemetry::{get_subscriber, init_subscriber}, web::app::start_server
 };
 
-use std::{error::Error, sync::Arc, time::Duration};
-use tokio::sync::Mutex;
+use std::{error::Error, path::PathBuf, time::Duration};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -20,29 +19,63 @@ struct Args {
     /// Question to ask the model
     #[arg(short, long, default_value = "What is the origin of the name wesley")]
     query: String,
+
+    /// Append monitor snapshots to this file so they can be replayed later
+    #[arg(long)]
+    record: Option<PathBuf>,
+
+    /// Replay a recording instead of monitoring this machine
+    #[arg(long)]
+    replay: Option<PathBuf>,
+
+    /// Playback speed multiplier for --replay
+    #[arg(long, default_value_t = 1.0)]
+    replay_speed: f64,
+
+    /// Start the replay over once the recording ends
+    #[arg(long)]
+    replay_loop: bool,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
 
-    let subscriber = get_subscriber("odin".into(), "info".into(), std::io::stdout);
+    let subscriber = get_subscriber("info".into(), std::io::stdout);
     init_subscriber(subscriber);
 
     tracing::info!("System Monitor Starting");
     let settings = get_configuration().expect("Failed to read configuration.");
 
-    let monitor = SystemMonitor::new(settings.clone());
+    let monitor = match args.replay {
+        Some(_) => SystemMonitor::for_replay(settings.clone()),
+        None => SystemMonitor::new(settings.clone()),
+    }?;
     tokio::spawn({
         let monitor = monitor.clone();
+        let replay = args.replay.clone();
         async move {
             // So we spawn monitoring tasks which will spawn sub tasks for each monitor and then we await
             // which essentially is blocking so sub tasks can run forever until killed
-            if let Err(e) = monitor.run().await {
+            let result = match replay {
+                Some(path) => monitor.replay(path, args.replay_speed, args.replay_loop).await,
+                None => monitor.run().await,
+            };
+            if let Err(e) = result {
                 tracing::error!("Monitor error: {}", e);
             }
         }
     });
 
+    if let Some(path) = args.record.clone() {
+        let monitor = monitor.clone();
+        tokio::spawn(async move {
+            if let Err(e) = monitor.record(path, Duration::from_secs(5)).await {
+                tracing::error!("Recording error: {}", e);
+            }
+        });
+    }
+
     let web_monitor = monitor.clone();
     tokio::spawn(async move {
         loop {
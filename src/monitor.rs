@@ -1,95 +1,323 @@
 use crate::{
-    cache::{blob::Cache, get_cached_data},
-    configuration::Settings,
-    ollama::{OllamaClient, ProcessInfo, ProcessScore},
+    cache::RingBuffer,
+    collector::{CollectorObserver, CollectorRegistry},
+    configuration::{Settings, StorageSettings},
+    events::{run_proc_connector, EventLog, EventQuery, ProcessEvent},
+    ollama::{OllamaClient, ProcessScore, PROCESS_ANALYSIS_PROMPT_VERSION},
     os_tooling::{
-        cpu::{get_current_cpu_usage, CPUGroup},
-        disk::{get_disk_usage, DiskGroup},
-        memory::{get_system_memory, SystemMemory},
-        network::{get_network_information, NetworkInterfaceGroup},
-        process::OsProcessGroup,
-        SystemScanner,
+        command_line::{command_line_threat, COMMAND_LINE_HEURISTICS_VERSION},
+        cpu::{CPUGroup, CpuCollector},
+        disk::{DiskCollector, DiskGroup},
+        filter::ProcessFilter,
+        lineage::LineageAttribute,
+        memory::{MemoryCollector, SystemMemory},
+        network::{NetworkCollector, NetworkInterfaceGroup},
+        process::{
+            process_usage, recorded_usage, restore_usage, OsProcessGroup, OsProcessInformation,
+            ProcessIdentity, ProcessUsage, RecordedUsage,
+        },
+        process_tree::ProcessTree,
+        scanner::SCAN_INTERVAL,
+        MetadataTags, SystemScanner,
     },
+    storage::{
+        downsample, Resolution, Rollup, RollupFn, RollupMetrics, Rollups, Sample, TimeSeriesStore,
+    },
+    utils::{append_to_json_lines, read_json_lines},
 };
 use anyhow::Result;
-use chrono::Local;
-use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, sync::Arc, time::Duration};
-use sysinfo::System;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use thiserror::Error;
 use tokio::sync::Mutex;
 
-// New analysis store for Ollama results
+// How long verdicts are kept, processes that weren't analysed in that time are forgotten
+const ANALYSIS_RETENTION_HOURS: i64 = 24;
+// Upper bound on verdict changes kept per process within the retention window
+const ANALYSIS_HISTORY_PER_PROCESS: usize = 500;
+
+// New analysis store for Ollama results, keeps a score timeline per process
 pub struct AnalysisStore {
-    cache: Arc<Mutex<HashMap<u32, ProcessAnalysis>>>,
+    cache: Arc<Mutex<HashMap<ProcessIdentity, AnalysisHistory>>>,
+    retention: chrono::Duration,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+// Analysis repeats every few seconds, only verdicts that differ from the last one are kept
+struct AnalysisHistory {
+    verdicts: RingBuffer<ProcessAnalysis>,
+    last_seen: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ProcessAnalysis {
     name: String,
     score: u8,
     reason: String,
+    // Which model and prompt produced this verdict, scores aren't comparable across them
+    model: String,
+    prompt_version: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProcessAnalysisHistory {
+    pub identity: ProcessIdentity,
+    pub verdicts: Vec<Sample<ProcessAnalysis>>,
+}
+
+impl Default for AnalysisStore {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl AnalysisStore {
     pub fn new() -> Self {
         Self {
             cache: Arc::new(Mutex::new(HashMap::new())),
+            retention: chrono::Duration::hours(ANALYSIS_RETENTION_HOURS),
         }
     }
 
-    pub async fn update(&self, analyses: Vec<ProcessScore>) {
+    pub fn with_retention(mut self, retention: chrono::Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub async fn update(
+        &self,
+        analyses: Vec<(ProcessIdentity, ProcessScore)>,
+        model: &str,
+        prompt_version: &str,
+    ) {
+        let now = Utc::now();
         let mut cache = self.cache.lock().await;
-        for analysis in analyses {
-            cache.insert(
-                analysis.pid,
-                ProcessAnalysis {
-                    name: analysis.name,
-                    score: analysis.score,
-                    reason: analysis.reason,
-                },
-            );
+        for (identity, analysis) in analyses {
+            let verdict = ProcessAnalysis {
+                name: analysis.name,
+                score: analysis.score,
+                reason: analysis.reason,
+                model: model.to_string(),
+                prompt_version: prompt_version.to_string(),
+            };
+            let history = cache.entry(identity).or_insert_with(|| AnalysisHistory {
+                verdicts: RingBuffer::new(ANALYSIS_HISTORY_PER_PROCESS),
+                last_seen: now,
+            });
+            history.last_seen = now;
+            if history.verdicts.latest().map(|latest| &latest.value) != Some(&verdict) {
+                history.verdicts.push(Sample {
+                    timestamp: now,
+                    value: verdict,
+                });
+            }
         }
+
+        // Forget processes that weren't analysed within retention, mostly dead pids. Live ones keep
+        // their current verdict however old it is
+        let cutoff = now - self.retention;
+        cache.retain(|_, history| {
+            if history.last_seen < cutoff {
+                return false;
+            }
+            let current = history.verdicts.latest().map(|latest| latest.timestamp);
+            history.verdicts.evict_before(current.map_or(cutoff, |current| current.min(cutoff)));
+            true
+        });
+    }
+
+    pub async fn get_analysis(&self, identity: &ProcessIdentity) -> Option<ProcessAnalysis> {
+        let cache = self.cache.lock().await;
+        cache
+            .get(identity)
+            .and_then(|history| history.verdicts.latest())
+            .map(|sample| sample.value.clone())
     }
 
-    pub async fn get_analysis(&self, pid: u32) -> Option<ProcessAnalysis> {
+    /**
+     Every verdict change still in retention for a pid, oldest first. Without a start time the most
+     recent process to hold that pid is used.
+    **/
+    pub async fn get_history(
+        &self,
+        pid: u32,
+        start_time: Option<u64>,
+    ) -> Option<ProcessAnalysisHistory> {
         let cache = self.cache.lock().await;
-        cache.get(&pid).cloned()
+        let (identity, history) = cache
+            .iter()
+            .filter(|(identity, _)| identity.pid == pid)
+            .filter(|(identity, _)| start_time.is_none_or(|start| identity.start_time == start))
+            .max_by_key(|(identity, _)| identity.start_time)?;
+        Some(ProcessAnalysisHistory {
+            identity: identity.clone(),
+            verdicts: history.verdicts.iter().cloned().collect(),
+        })
     }
 }
 
 // Shared metric storage for each resource type
 pub struct MetricStore<T> {
-    cache: Arc<Mutex<Cache<String, T>>>,
+    buffer: Arc<Mutex<RingBuffer<T>>>,
+    // Samples older than this are never returned even if the buffer still has room
+    max_age: chrono::Duration,
+    // Optional on disk history, recent values are still served from the buffer
+    persistence: Option<Arc<Mutex<TimeSeriesStore>>>,
+    // Optional downsampled history, kept far longer than the raw buffer
+    rollups: Option<Arc<Mutex<Rollups>>>,
+    rollup_metrics: Option<RollupFn<T>>,
 }
 
-impl<T: Clone + std::fmt::Debug> MetricStore<T> {
-    pub fn new(ttl_seconds: u64) -> Self {
+impl<T: Clone + std::fmt::Debug + Serialize + DeserializeOwned> MetricStore<T> {
+    pub fn new(capacity: usize, ttl_seconds: u64) -> Self {
         Self {
-            cache: Arc::new(Mutex::new(Cache::new(ttl_seconds))),
+            buffer: Arc::new(Mutex::new(RingBuffer::new(capacity))),
+            max_age: chrono::Duration::seconds(ttl_seconds as i64),
+            persistence: None,
+            rollups: None,
+            rollup_metrics: None,
         }
     }
 
+    pub fn with_persistence(mut self, store: TimeSeriesStore) -> Self {
+        self.persistence = Some(Arc::new(Mutex::new(store)));
+        self
+    }
+
     pub async fn store(&self, metric_type: &str, value: T) {
-        let mut cache = self.cache.lock().await;
-        // Include metric type in key to prevent collisions
-        let key = format!("{}_{}", metric_type, Local::now());
-        cache.insert(key, value);
-        // cache.remove_expired();
+        self.store_sample(metric_type, Sample::now(value)).await;
+    }
+
+    /// Stores a sample taken at its own timestamp rather than now, e.g. a replayed one
+    pub async fn store_sample(&self, metric_type: &str, sample: Sample<T>) {
+        if let Some(persistence) = &self.persistence {
+            if let Err(e) = persistence.lock().await.append(&sample) {
+                tracing::error!("Failed to persist {} sample: {}", metric_type, e);
+            }
+        }
+        if let (Some(rollups), Some(rollup_metrics)) = (&self.rollups, self.rollup_metrics) {
+            rollups
+                .lock()
+                .await
+                .add(sample.timestamp, &rollup_metrics(&sample.value));
+        }
+        let mut buffer = self.buffer.lock().await;
+        buffer.evict_before(sample.timestamp - self.max_age);
+        buffer.push(sample);
     }
 
+    /// Up to `count` values newest first
     pub async fn get_recent(&self, count: usize) -> Vec<T> {
-        let cache = self.cache.lock().await;
-        get_cached_data(&cache).into_iter().take(count).collect()
+        let cutoff = Utc::now() - self.max_age;
+        let buffer = self.buffer.lock().await;
+        buffer
+            .recent(count)
+            .take_while(|s| s.timestamp >= cutoff)
+            .map(|s| s.value.clone())
+            .collect()
+    }
+
+    pub async fn latest(&self) -> Option<Sample<T>> {
+        let cutoff = Utc::now() - self.max_age;
+        let buffer = self.buffer.lock().await;
+        buffer.latest().filter(|s| s.timestamp >= cutoff).cloned()
+    }
+
+    /// In memory samples between `from` and `to`, oldest first
+    pub async fn range(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<Sample<T>> {
+        let from = from.max(Utc::now() - self.max_age);
+        let buffer = self.buffer.lock().await;
+        buffer.range(from, to).cloned().collect()
+    }
+
+    /// In memory samples from the last `window`, oldest first
+    pub async fn window(&self, window: Duration) -> Vec<Sample<T>> {
+        let now = Utc::now();
+        let window = chrono::Duration::from_std(window).unwrap_or(chrono::TimeDelta::MAX);
+        self.range(now.checked_sub_signed(window).unwrap_or(DateTime::<Utc>::MIN_UTC), now)
+            .await
+    }
+
+    /// Persisted samples between `from` and `to`, falls back to the buffer when the store is memory only
+    pub async fn history(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<Sample<T>>> {
+        match &self.persistence {
+            Some(persistence) => persistence.lock().await.range(from, to),
+            None => Ok(self.range(from, to).await),
+        }
+    }
+
+    /// Downsampled buckets between `from` and `to`, empty when the store has no rollups
+    pub async fn rollups(
+        &self,
+        resolution: Resolution,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Vec<Sample<RollupMetrics>> {
+        match &self.rollups {
+            Some(rollups) => rollups.lock().await.buckets(resolution, from, to),
+            None => vec![],
+        }
+    }
+
+    pub async fn run_maintenance(&self) -> Result<()> {
+        if let Some(persistence) = &self.persistence {
+            let mut persistence = persistence.lock().await;
+            let expired = persistence.enforce_retention()?;
+            let compacted = persistence.compact()?;
+            tracing::debug!(
+                "Storage maintenance removed {} expired and {} compacted segments",
+                expired,
+                compacted
+            );
+        }
+        if let Some(rollups) = &self.rollups {
+            rollups.lock().await.run_maintenance()?;
+        }
+        Ok(())
     }
 }
 
-#[derive(Serialize, Default, Deserialize, Debug)]
+impl<T: Rollup + Clone + std::fmt::Debug + Serialize + DeserializeOwned> MetricStore<T> {
+    pub fn with_rollups(mut self) -> Self {
+        // Persisted stores keep their rollups next to the raw segments so long range history survives restarts
+        let rollups = match &self.persistence {
+            Some(persistence) => persistence
+                .try_lock()
+                .map_err(anyhow::Error::from)
+                .and_then(|persistence| Rollups::open(&persistence))
+                .unwrap_or_else(|e| {
+                    tracing::error!("Failed to open rollup storage, keeping it in memory only: {}", e);
+                    Rollups::new()
+                }),
+            None => Rollups::new(),
+        };
+        self.rollups = Some(Arc::new(Mutex::new(rollups)));
+        self.rollup_metrics = Some(T::metrics);
+        self
+    }
+}
+
+#[derive(Serialize, Default, Deserialize, Debug, Clone)]
 pub struct MonitorOutput {
     pub processes: Vec<OsProcessGroup>,
+    #[serde(default)]
+    pub process_tree: ProcessTree,
+    // Seen by the proc connector but gone before a scan could pick them up
+    #[serde(default)]
+    pub transient_processes: Vec<OsProcessInformation>,
     pub cpu: CPUGroup,
     pub memory: SystemMemory,
     pub disks: DiskGroup,
     pub network: NetworkInterfaceGroup,
+    // Latest sample of any other registered collector, keyed by its name
+    #[serde(flatten)]
+    pub collectors: BTreeMap<String, serde_json::Value>,
 }
 
 impl MonitorOutput {
@@ -102,6 +330,16 @@ impl MonitorOutput {
         self
     }
 
+    pub fn with_process_tree(mut self, process_tree: ProcessTree) -> Self {
+        self.process_tree = process_tree;
+        self
+    }
+
+    pub fn with_transient_processes(mut self, processes: Vec<OsProcessInformation>) -> Self {
+        self.transient_processes = processes;
+        self
+    }
+
     pub fn with_cpu(mut self, cpus: CPUGroup) -> Self {
         self.cpu = cpus;
         self
@@ -121,217 +359,199 @@ impl MonitorOutput {
         self.network = networks;
         self
     }
-}
-
-// Individual monitor implementations
-pub struct ProcessMonitor {
-    system: System,
-    store: Arc<MetricStore<Vec<OsProcessGroup>>>,
-}
-
-impl ProcessMonitor {
-    pub fn new(store: Arc<MetricStore<Vec<OsProcessGroup>>>) -> Self {
-        Self {
-            system: System::new_all(),
-            store,
-        }
-    }
 
-    pub async fn run(mut self, interval: Duration) {
-        let mut ticker = tokio::time::interval(interval);
-        loop {
-            ticker.tick().await;
-            match self.collect().await {
-                Ok(processes) => {
-                    self.store.store("process", processes).await;
-                }
-                Err(e) => {
-                    tracing::error!("Failed to collect process metrics: {}", e);
-                }
+    pub fn with_collector<T: Serialize>(mut self, name: &str, value: &T) -> Self {
+        match serde_json::to_value(value) {
+            Ok(value) => {
+                self.collectors.insert(name.to_string(), value);
             }
+            Err(e) => tracing::error!("Failed to serialize {}: {}", name, e),
         }
+        self
     }
 
-    async fn collect(&mut self) -> Result<Vec<OsProcessGroup>> {
-        let scanner = SystemScanner::new();
-        let mut results = scanner.scan_running_proccess()?;
-        scanner.apply_attributes(&mut results);
-        Ok(results)
-    }
-}
-
-pub struct CPUMonitor {
-    system: System,
-    store: Arc<MetricStore<CPUGroup>>,
-}
-
-impl CPUMonitor {
-    pub fn new(store: Arc<MetricStore<CPUGroup>>) -> Self {
-        Self {
-            system: System::new_all(),
-            store,
-        }
-    }
-
-    pub async fn run(mut self, interval: Duration) {
-        let mut ticker = tokio::time::interval(interval);
-        loop {
-            ticker.tick().await;
-            let metrics = self.collect();
-            self.store.store("cpu", metrics).await;
-        }
+    pub fn with_collectors(mut self, collectors: BTreeMap<String, serde_json::Value>) -> Self {
+        self.collectors = collectors;
+        self
     }
 
-    fn collect(&mut self) -> CPUGroup {
-        get_current_cpu_usage(&mut self.system)
+    /// Typed view of a collector's sample, None when it has none or `T` doesn't match
+    pub fn collector<T: DeserializeOwned>(&self, name: &str) -> Option<T> {
+        serde_json::from_value(self.collectors.get(name)?.clone()).ok()
     }
 }
 
-pub struct MemoryMonitor {
-    system: System,
-    store: Arc<MetricStore<SystemMemory>>,
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryResource {
+    Cpu,
+    Memory,
+    Disk,
+    Network,
+    Process,
+    // Any other registered collector with rollups
+    #[serde(untagged)]
+    Collector(String),
 }
 
-impl MemoryMonitor {
-    pub fn new(store: Arc<MetricStore<SystemMemory>>) -> Self {
-        Self {
-            system: System::new_all(),
-            store,
-        }
-    }
-
-    pub async fn run(mut self, interval: Duration) {
-        let mut ticker = tokio::time::interval(interval);
-        loop {
-            ticker.tick().await;
-            let metrics = self.collect();
-            self.store.store("memory", metrics).await;
-        }
-    }
-
-    fn collect(&mut self) -> SystemMemory {
-        get_system_memory(&mut self.system)
-    }
+#[derive(Deserialize, Debug, Default)]
+pub struct HistoryQuery {
+    // Defaults to the last hour
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    // Bucket size in seconds, 60/300/3600 are served from rollups, anything else from raw samples
+    pub step: Option<i64>,
+    // Required for process history
+    pub pid: Option<u32>,
+    // Pins process history to one process when the pid has been reused
+    pub start_time: Option<u64>,
 }
 
-pub struct NetworkMonitor {
-    system: System,
-    store: Arc<MetricStore<NetworkInterfaceGroup>>,
+#[derive(Error, Debug)]
+pub enum HistoryError {
+    #[error("from must be before to")]
+    InvalidRange,
+    #[error("pid is required for process history")]
+    MissingPid,
+    #[error("{0} has no history")]
+    UnknownResource(String),
+    #[error("Failed to read history: {0}")]
+    Storage(#[from] anyhow::Error),
 }
 
-impl NetworkMonitor {
-    pub fn new(store: Arc<MetricStore<NetworkInterfaceGroup>>) -> Self {
-        Self {
-            system: System::new_all(),
-            store,
-        }
-    }
-
-    pub async fn run(mut self, interval: Duration) {
-        let mut ticker = tokio::time::interval(interval);
-        loop {
-            ticker.tick().await;
-            let metrics = self.collect();
-            self.store.store("network", metrics).await;
-        }
-    }
+// Everything derived from a process scan besides the scan itself
+struct ProcessObserver {
+    usage_store: Arc<MetricStore<Vec<ProcessUsage>>>,
+    events: Arc<EventLog>,
+}
 
-    fn collect(&mut self) -> NetworkInterfaceGroup {
-        get_network_information(&mut self.system)
+#[async_trait]
+impl CollectorObserver<Vec<OsProcessGroup>> for ProcessObserver {
+    async fn observe(&self, processes: &Vec<OsProcessGroup>) {
+        self.events.observe(processes).await;
+        self.usage_store
+            .store("process_usage", process_usage(processes))
+            .await;
     }
 }
 
-pub struct DiskMonitor {
-    system: System,
-    store: Arc<MetricStore<DiskGroup>>,
+// A recorded snapshot plus the process usage the API leaves out of it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordedFrame {
+    pub snapshot: MonitorOutput,
+    #[serde(default)]
+    pub usage: Vec<RecordedUsage>,
 }
 
-impl DiskMonitor {
-    pub fn new(store: Arc<MetricStore<DiskGroup>>) -> Self {
+impl From<MonitorOutput> for RecordedFrame {
+    fn from(snapshot: MonitorOutput) -> Self {
         Self {
-            system: System::new_all(),
-            store,
+            usage: recorded_usage(&snapshot.processes),
+            snapshot,
         }
     }
-
-    pub async fn run(mut self, interval: Duration) {
-        let mut ticker = tokio::time::interval(interval);
-        loop {
-            ticker.tick().await;
-            let metrics = self.collect();
-            self.store.store("disk", metrics).await;
-        }
-    }
-
-    fn collect(&mut self) -> DiskGroup {
-        get_disk_usage(&mut self.system)
-    }
 }
 
 // Main system monitor coordinates everything
 #[derive(Clone)]
 pub struct SystemMonitor {
     process_store: Arc<MetricStore<Vec<OsProcessGroup>>>,
-    cpu_store: Arc<MetricStore<CPUGroup>>,
-    memory_store: Arc<MetricStore<SystemMemory>>,
-    disk_store: Arc<MetricStore<DiskGroup>>,
-    network_store: Arc<MetricStore<NetworkInterfaceGroup>>,
+    process_usage_store: Arc<MetricStore<Vec<ProcessUsage>>>,
+    // Every collector, the process scan included. Its observer feeds usage history and events
+    collectors: Arc<CollectorRegistry>,
+    // The proc connector reports the same processes the scan keeps
+    filter: ProcessFilter,
     analysis_store: Arc<AnalysisStore>,
+    event_log: Arc<EventLog>,
     pub settings: Settings,
 }
 
 impl SystemMonitor {
-    pub fn new(settings: Settings) -> Self {
-        Self {
-            process_store: Arc::new(MetricStore::new(30)), // 5 min TTL
-            cpu_store: Arc::new(MetricStore::new(10)),     // 1 min TTL
-            memory_store: Arc::new(MetricStore::new(10)),
-            disk_store: Arc::new(MetricStore::new(300)),
-            network_store: Arc::new(MetricStore::new(10)),
+    /// Fails when the process filters or lineage rules in `settings` don't compile
+    pub fn new(settings: Settings) -> Result<Self> {
+        Self::with_retention(settings, PROCESS_RETENTION_SECS, RAW_RETENTION_SECS)
+    }
+
+    /**
+     Monitor for `replay` to feed. Nothing is persisted so a recording can't end up in this machine's
+     history, and samples are served however old their recorded timestamps are.
+    **/
+    pub fn for_replay(mut settings: Settings) -> Result<Self> {
+        settings.storage.enabled = false;
+        Self::with_retention(settings, REPLAY_RETENTION_SECS, REPLAY_RETENTION_SECS)
+    }
+
+    fn with_retention(settings: Settings, process_secs: u64, raw_secs: u64) -> Result<Self> {
+        let storage = &settings.storage;
+        let filter = ProcessFilter::new(&settings.filters)?;
+        let scanner = SystemScanner::new()
+            .with_filter(filter.clone())
+            .with_lineage(LineageAttribute::new(&settings.lineage)?);
+        let process_usage_store = Arc::new(persistent_store(720, "process", raw_secs, storage));
+        let event_log = Arc::new(EventLog::default());
+        let observer = ProcessObserver {
+            usage_store: Arc::clone(&process_usage_store),
+            events: Arc::clone(&event_log),
+        };
+
+        // Buffers are sized to hold RAW_RETENTION_SECS worth of samples at each collector's interval,
+        // full process trees are heavy so we only keep the last minute of those.
+        // Resource stores also roll up into 1m/5m/1h buckets for long range history
+        let collectors = resource_collectors(raw_secs, storage).register(
+            scanner,
+            MetricStore::new(12, process_secs),
+            Some(Arc::new(observer)),
+        );
+        let process_store = collectors
+            .store(PROCESS_COLLECTOR)
+            .expect("Process scanner is registered");
+        Ok(Self {
+            process_store,
+            process_usage_store,
+            collectors: Arc::new(collectors),
+            filter,
             analysis_store: Arc::new(AnalysisStore::new()),
+            event_log,
             settings,
-        }
+        })
     }
 
     pub async fn run(&self) -> Result<()> {
-        // Spawn process monitor
-        let process_monitor = ProcessMonitor::new(Arc::clone(&self.process_store));
-        tokio::spawn(async move {
-            // tokio::signal::ctrl_c().await.unwrap();
-            process_monitor.run(Duration::from_secs(5)).await;
-        });
-
-        // Spawn CPU monitor
-        let cpu_monitor = CPUMonitor::new(Arc::clone(&self.cpu_store));
-        tokio::spawn(async move {
-            // tokio::signal::ctrl_c().await.unwrap();
-            cpu_monitor.run(Duration::from_secs(2)).await;
-        });
-
-        // Spawn Disk monitor
-        let disk_monitor = DiskMonitor::new(Arc::clone(&self.disk_store));
-        tokio::spawn(async move {
-            // tokio::signal::ctrl_c().await.unwrap();
-            disk_monitor.run(Duration::from_secs(10)).await;
-        });
+        // Spawn process event listener, anything living shorter than a scan interval is transient
+        if self.settings.monitor.proc_connector {
+            let event_log = Arc::clone(&self.event_log);
+            let filter = self.filter.clone();
+            tokio::spawn(async move {
+                run_proc_connector(event_log, SCAN_INTERVAL, filter).await;
+            });
+        }
 
-        // Spawn Network monitor
-        let network_monitor = NetworkMonitor::new(Arc::clone(&self.network_store));
-        tokio::spawn(async move {
-            // tokio::signal::ctrl_c().await.unwrap();
-            network_monitor.run(Duration::from_secs(10)).await;
-        });
+        let collectors = self.collectors.spawn_all();
 
-        // Spawn Memory monitor
-        let memory_monitor = MemoryMonitor::new(Arc::clone(&self.memory_store));
-        tokio::spawn(async move {
-            // tokio::signal::ctrl_c().await.unwrap();
-            memory_monitor.run(Duration::from_secs(10)).await;
-        });
+        // Spawn storage retention and compaction
+        if self.settings.storage.enabled {
+            let monitor = self.clone();
+            let interval = Duration::from_secs(self.settings.storage.maintenance_interval_secs);
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(interval);
+                loop {
+                    ticker.tick().await;
+                    if let Err(e) = monitor.run_storage_maintenance().await {
+                        tracing::error!("Storage maintenance failed: {}", e);
+                    }
+                }
+            });
+        }
         tokio::signal::ctrl_c().await?;
+        collectors.abort();
         Ok(())
     }
 
+    async fn run_storage_maintenance(&self) -> Result<()> {
+        self.process_usage_store.run_maintenance().await?;
+        self.collectors.run_maintenance().await
+    }
+
     pub async fn run_analysis(&self, ollama: OllamaClient) -> Result<()> {
         let mut interval = tokio::time::interval(Duration::from_secs(5));
         loop {
@@ -341,18 +561,250 @@ impl SystemMonitor {
             if let Some(processes) = self.process_store.get_recent(1).await.pop() {
                 // Convert to ProcessInfo for Ollama
 
+                // Scores only carry a pid, tie them back to the exact process we sent
+                let identities: HashMap<u32, ProcessIdentity> = processes
+                    .iter()
+                    .map(|p| (p.parent_process.pid, p.parent_process.identity.clone()))
+                    .collect();
+                // Command line matches raise the model's score, anything the model didn't score
+                // (children, or every process when it is down) keeps them as a verdict of their own
+                let mut heuristics = command_line_scores(&processes);
+
                 // Get analysis from Ollama
                 match ollama.analyze_process_names(&processes).await {
                     Ok(scores) => {
                         // Store in analysis store instead of process store
                         tracing::info!("Saving Scores");
-                        self.analysis_store.update(scores).await;
+                        let scores = scores
+                            .into_iter()
+                            .filter_map(|score| {
+                                let identity = identities.get(&score.pid)?.clone();
+                                let score = match heuristics.remove(&identity) {
+                                    Some(heuristic) => with_heuristic(score, heuristic),
+                                    None => score,
+                                };
+                                Some((identity, score))
+                            })
+                            .collect();
+                        self.analysis_store
+                            .update(
+                                scores,
+                                &ollama.settings.monitor.model,
+                                PROCESS_ANALYSIS_PROMPT_VERSION,
+                            )
+                            .await;
                     }
                     Err(e) => {
                         tracing::error!("Failed to analyze processes: {}", e);
                     }
                 }
+                self.analysis_store
+                    .update(
+                        heuristics.into_iter().collect(),
+                        HEURISTIC_MODEL,
+                        COMMAND_LINE_HEURISTICS_VERSION,
+                    )
+                    .await;
+            }
+        }
+    }
+
+    /// Threat score timeline for a single process, oldest first
+    pub async fn get_analysis_history(
+        &self,
+        pid: u32,
+        start_time: Option<u64>,
+    ) -> Option<ProcessAnalysisHistory> {
+        self.analysis_store.get_history(pid, start_time).await
+    }
+
+    /// Process lifecycle events matching `query`, oldest first
+    pub async fn get_events(&self, query: &EventQuery) -> Vec<Sample<ProcessEvent>> {
+        self.event_log.query(query).await
+    }
+
+    /// Live feed of process lifecycle events, for anything that wants to react as they happen
+    pub fn subscribe_events(&self) -> tokio::sync::broadcast::Receiver<Sample<ProcessEvent>> {
+        self.event_log.subscribe()
+    }
+
+    /**
+     Time series for a single resource between `from` and `to`.
+
+     Every resource comes back in the rollup bucket shape, raw samples are single value buckets.
+     Process history is per pid since storing every process per tick is already heavy enough.
+    **/
+    pub async fn get_history(
+        &self,
+        resource: HistoryResource,
+        query: &HistoryQuery,
+    ) -> Result<Vec<Sample<RollupMetrics>>, HistoryError> {
+        let to = query.to.unwrap_or_else(Utc::now);
+        let from = query.from.unwrap_or(to - chrono::Duration::hours(1));
+        if from > to {
+            return Err(HistoryError::InvalidRange);
+        }
+
+        let name = match &resource {
+            HistoryResource::Cpu => "cpu",
+            HistoryResource::Memory => "memory",
+            HistoryResource::Disk => "disk",
+            HistoryResource::Network => "network",
+            HistoryResource::Collector(name) => name,
+            HistoryResource::Process => return self.process_history(query, from, to).await,
+        };
+        self.collectors
+            .history(name, from, to, query.step)
+            .await
+            .ok_or_else(|| HistoryError::UnknownResource(name.to_string()))?
+            .map_err(HistoryError::Storage)
+    }
+
+    // Process usage isn't a collector of its own, it is pulled out of the stored scans per pid
+    async fn process_history(
+        &self,
+        query: &HistoryQuery,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Sample<RollupMetrics>>, HistoryError> {
+        let pid = query.pid.ok_or(HistoryError::MissingPid)?;
+        let samples = self
+            .process_usage_store
+            .history(from, to)
+            .await?
+            .into_iter()
+            .filter_map(|sample| {
+                let usage = sample.value.into_iter().find(|p| {
+                    p.pid == pid && query.start_time.is_none_or(|start| p.start_time == start)
+                })?;
+                Some(Sample {
+                    timestamp: sample.timestamp,
+                    value: vec![
+                        ("cpu", usage.cpu as f64),
+                        ("memory_usage", usage.memory_usage as f64),
+                        ("disk_read_rate", usage.disk_read_rate),
+                        ("disk_write_rate", usage.disk_write_rate),
+                    ],
+                })
+            })
+            .collect();
+        Ok(downsample(samples, query.step))
+    }
+
+    /// Appends the latest snapshot to `path` every `interval` so it can be replayed later
+    pub async fn record(&self, path: PathBuf, interval: Duration) -> Result<()> {
+        tracing::info!("Recording snapshots to {}", path.display());
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let snapshot = self.get_latest_snapshot().await;
+            // Nothing collected yet, no point recording an empty frame
+            if snapshot.processes.is_empty() && snapshot.cpu.cpus.is_empty() {
+                continue;
+            }
+            let frame = Sample::now(RecordedFrame::from(snapshot));
+            if let Err(e) = append_to_json_lines(&frame, &path).await {
+                tracing::error!("Failed to record snapshot to {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    /**
+     Feeds a recording into the stores in place of the live monitors so the API and analysis loop
+     see it as if it were this machine. The original spacing between snapshots is kept, divided by
+     `speed`, and with `repeat` the recording starts over once it ends.
+
+     Samples keep their recorded timestamps, each repeat is shifted past the previous one so history
+     stays in order. Use a monitor from `for_replay` so none of it is persisted.
+    **/
+    pub async fn replay(&self, path: PathBuf, speed: f64, repeat: bool) -> Result<()> {
+        if !(speed > 0.0 && speed.is_finite()) {
+            return Err(anyhow::anyhow!("Replay speed must be positive, got {}", speed));
+        }
+        let recording: Vec<Sample<RecordedFrame>> = read_json_lines(&path)?;
+        if recording.is_empty() {
+            return Err(anyhow::anyhow!("Recording {} has no snapshots", path.display()));
+        }
+        tracing::info!(
+            "Replaying {} snapshots from {}",
+            recording.len(),
+            path.display()
+        );
+
+        // A repeat starts one snapshot interval after the previous pass ended
+        let first = recording[0].timestamp;
+        let interval = recording
+            .get(1)
+            .map(|second| second.timestamp - first)
+            .unwrap_or(chrono::Duration::seconds(1));
+        let pass_length = recording[recording.len() - 1].timestamp - first + interval;
+
+        let mut offset = chrono::Duration::zero();
+        loop {
+            let mut previous: Option<DateTime<Utc>> = None;
+            for frame in &recording {
+                if let Some(previous) = previous {
+                    let gap = (frame.timestamp - previous).to_std().unwrap_or_default();
+                    tokio::time::sleep(gap.div_f64(speed)).await;
+                }
+                previous = Some(frame.timestamp);
+                let RecordedFrame {
+                    mut snapshot,
+                    usage,
+                } = frame.value.clone();
+                restore_usage(&mut snapshot.processes, &usage);
+                self.ingest_snapshot(frame.timestamp + offset, snapshot)
+                    .await;
+            }
+            if !repeat {
+                tracing::info!("Replay of {} finished", path.display());
+                return Ok(());
             }
+            tokio::time::sleep(interval.to_std().unwrap_or_default().div_f64(speed)).await;
+            offset += pass_length;
+        }
+    }
+
+    async fn ingest_snapshot(&self, timestamp: DateTime<Utc>, snapshot: MonitorOutput) {
+        self.event_log
+            .observe_at(&snapshot.processes, timestamp)
+            .await;
+        self.process_usage_store
+            .store_sample(
+                "process_usage",
+                Sample {
+                    timestamp,
+                    value: process_usage(&snapshot.processes),
+                },
+            )
+            .await;
+        self.process_store
+            .store_sample(
+                "process",
+                Sample {
+                    timestamp,
+                    value: snapshot.processes,
+                },
+            )
+            .await;
+        self.store_resource("cpu", timestamp, snapshot.cpu).await;
+        self.store_resource("memory", timestamp, snapshot.memory).await;
+        self.store_resource("disk", timestamp, snapshot.disks).await;
+        self.store_resource("network", timestamp, snapshot.network).await;
+        for (name, value) in snapshot.collectors {
+            let sample = Sample { timestamp, value };
+            if let Err(e) = self.collectors.store_json(&name, sample).await {
+                tracing::warn!("Skipping replayed {}: {}", name, e);
+            }
+        }
+    }
+
+    async fn store_resource<T>(&self, name: &str, timestamp: DateTime<Utc>, value: T)
+    where
+        T: Clone + std::fmt::Debug + Serialize + DeserializeOwned + 'static,
+    {
+        if let Some(store) = self.collectors.store::<T>(name) {
+            store.store_sample(name, Sample { timestamp, value }).await;
         }
     }
 
@@ -365,50 +817,134 @@ impl SystemMonitor {
             .pop()
             .unwrap_or_default();
 
-        // Enrich processes with analysis data
+        // Enrich processes with analysis data, children only have command line heuristics
         let mut enriched_processes = processes.clone();
         for process in &mut enriched_processes {
-            if let Some(analysis) = self
-                .analysis_store
-                .get_analysis(process.parent_process.pid)
-                .await
+            for process in
+                std::iter::once(&mut process.parent_process).chain(process.children.iter_mut())
             {
-                tracing::info!("Updating process with threat score {:?}", process.parent_process);
-                process.parent_process.attributes.insert(
-                    crate::os_tooling::MetadataTags::ThreatScore,
-                    analysis.score.to_string(),
-                );
-                process.parent_process.attributes.insert(
-                    crate::os_tooling::MetadataTags::ThreatScoreReason,
-                    analysis.reason,
-                );
+                let analysis = self.analysis_store.get_analysis(&process.identity).await;
+                apply_threat_score(process, analysis);
             }
         }
+        let mut transient_processes = self.event_log.transient_processes().await;
+        for process in &mut transient_processes {
+            apply_threat_score(process, None);
+        }
 
         MonitorOutput::new()
+            .with_process_tree(ProcessTree::from_groups(&enriched_processes))
             .with_processes(enriched_processes)
-            .with_cpu(self.cpu_store.get_recent(1).await.pop().unwrap_or_default())
-            .with_memory(
-                self.memory_store
-                    .get_recent(1)
-                    .await
-                    .pop()
-                    .unwrap_or_default(),
-            )
-            .with_disks(
-                self.disk_store
-                    .get_recent(1)
-                    .await
-                    .pop()
-                    .unwrap_or_default(),
-            )
-            .with_networks(
-                self.network_store
-                    .get_recent(1)
-                    .await
-                    .pop()
-                    .unwrap_or_default(),
-            )
+            .with_transient_processes(transient_processes)
+            .with_cpu(self.collectors.latest("cpu").await.unwrap_or_default())
+            .with_memory(self.collectors.latest("memory").await.unwrap_or_default())
+            .with_disks(self.collectors.latest("disk").await.unwrap_or_default())
+            .with_networks(self.collectors.latest("network").await.unwrap_or_default())
+            .with_collectors(self.collectors.latest_json(&BUILTIN_COLLECTORS).await)
+    }
+}
+
+// Model recorded for verdicts that only come from command line heuristics
+const HEURISTIC_MODEL: &str = "command_line_heuristics";
+
+// Heuristic verdict for every scanned process whose command line matched
+fn command_line_scores(groups: &[OsProcessGroup]) -> HashMap<ProcessIdentity, ProcessScore> {
+    groups
+        .iter()
+        .flat_map(|group| std::iter::once(&group.parent_process).chain(&group.children))
+        .filter_map(|process| {
+            let (score, reason) = command_line_threat(&process.attributes)?;
+            let score = ProcessScore {
+                pid: process.pid,
+                name: process.name.clone(),
+                score,
+                reason,
+            };
+            Some((process.identity.clone(), score))
+        })
+        .collect()
+}
+
+// Command line heuristics can raise the model's score but never lower it
+fn with_heuristic(mut score: ProcessScore, heuristic: ProcessScore) -> ProcessScore {
+    score.score = score.score.max(heuristic.score);
+    score.reason = format!("{}; {}", score.reason, heuristic.reason);
+    score
+}
+
+// Stored verdicts already include the heuristics, live ones cover anything not analysed yet
+fn apply_threat_score(process: &mut OsProcessInformation, analysis: Option<ProcessAnalysis>) {
+    let (score, reason) = match analysis {
+        Some(analysis) => (analysis.score, analysis.reason),
+        None => match command_line_threat(&process.attributes) {
+            Some(heuristic) => heuristic,
+            None => return,
+        },
+    };
+    process
+        .attributes
+        .insert(MetadataTags::ThreatScore, score.to_string());
+    process
+        .attributes
+        .insert(MetadataTags::ThreatScoreReason, reason);
+}
+
+// The scan's name, its processes get their own enriched fields on MonitorOutput
+const PROCESS_COLLECTOR: &str = "process";
+// Collectors with their own field on MonitorOutput
+const BUILTIN_COLLECTORS: [&str; 5] = [PROCESS_COLLECTOR, "cpu", "memory", "disk", "network"];
+
+// Every resource collector with its store, new resources only need registering here
+fn resource_collectors(raw_secs: u64, storage: &StorageSettings) -> CollectorRegistry {
+    CollectorRegistry::new()
+        .register_with_rollups(
+            CpuCollector::new(),
+            persistent_store(1800, "cpu", raw_secs, storage),
+            None,
+        )
+        .register_with_rollups(
+            MemoryCollector::default(),
+            persistent_store(360, "memory", raw_secs, storage),
+            None,
+        )
+        .register_with_rollups(
+            DiskCollector,
+            persistent_store(360, "disk", raw_secs, storage),
+            None,
+        )
+        .register_with_rollups(
+            NetworkCollector::new(),
+            persistent_store(360, "network", raw_secs, storage),
+            None,
+        )
+}
+
+// How long raw samples stay in memory, anything older comes from disk
+const RAW_RETENTION_SECS: u64 = 3600;
+const PROCESS_RETENTION_SECS: u64 = 60;
+// Recordings can be arbitrarily old, replayed samples should never age out of the buffers
+const REPLAY_RETENTION_SECS: u64 = 10 * 365 * 24 * 3600;
+
+// Builds a metric store that also writes to disk when storage is enabled
+fn persistent_store<T>(
+    capacity: usize,
+    resource: &str,
+    raw_secs: u64,
+    settings: &StorageSettings,
+) -> MetricStore<T>
+where
+    T: Clone + std::fmt::Debug + Serialize + DeserializeOwned,
+{
+    let store = MetricStore::new(capacity, raw_secs);
+    if !settings.enabled {
+        return store;
+    }
+    match TimeSeriesStore::open(Path::new(&settings.path).join(resource), settings) {
+        Ok(persistence) => store.with_persistence(persistence),
+        Err(e) => {
+            tracing::error!("Failed to open {} storage, keeping it in memory only: {}", resource, e);
+            store
+        }
     }
 }
 
@@ -419,7 +955,7 @@ mod tests {
 
     #[test]
     async fn test_metric_store_basic_operations() {
-        let store = MetricStore::<i32>::new(10); // 10 second TTL
+        let store = MetricStore::<i32>::new(10, 10); // 10 second TTL
 
         // Test storing and retrieving
         store.store("test_metric", 42).await;
@@ -430,7 +966,7 @@ mod tests {
 
     #[test]
     async fn test_metric_store_ttl() {
-        let store = MetricStore::<String>::new(1); // 1 second TTL
+        let store = MetricStore::<String>::new(10, 1); // 1 second TTL
 
         store.store("test_metric", "data".to_string()).await;
         tokio::time::sleep(Duration::from_secs(2)).await;
@@ -439,6 +975,190 @@ mod tests {
         assert_eq!(recent.len(), 0, "Store should be empty after TTL");
     }
 
+    #[test]
+    async fn test_metric_store_is_bounded_and_newest_first() {
+        let store = MetricStore::<i32>::new(3, 60);
+        for value in 0..5 {
+            store.store("test_metric", value).await;
+        }
+
+        assert_eq!(store.get_recent(10).await, vec![4, 3, 2]);
+        assert_eq!(store.latest().await.map(|s| s.value), Some(4));
+        let window: Vec<i32> = store
+            .window(Duration::from_secs(60))
+            .await
+            .into_iter()
+            .map(|s| s.value)
+            .collect();
+        assert_eq!(window, vec![2, 3, 4]);
+    }
+
+    #[test]
+    async fn test_metric_store_rollups() {
+        let store = MetricStore::<SystemMemory>::new(10, 60).with_rollups();
+        for used_memory in [100, 200, 300] {
+            let memory = SystemMemory {
+                total_memory: 1000,
+                used_memory,
+                ..SystemMemory::default()
+            };
+            store.store("memory", memory).await;
+        }
+
+        let buckets = store
+            .rollups(
+                Resolution::OneHour,
+                Utc::now() - chrono::Duration::hours(1),
+                Utc::now(),
+            )
+            .await;
+        let used = &buckets.last().unwrap().value["used_memory"];
+        assert_eq!(used.count, 3);
+        assert_eq!(used.avg, 200.0);
+        assert_eq!(used.max, 300.0);
+    }
+
+    #[test]
+    async fn test_analysis_store_keeps_score_timeline() {
+        let store = AnalysisStore::new();
+        let identity = ProcessIdentity::new(42, 1000);
+        for score in [10, 80] {
+            let analysis = ProcessScore {
+                pid: 42,
+                name: "miner".to_string(),
+                score,
+                reason: "test".to_string(),
+            };
+            store
+                .update(vec![(identity.clone(), analysis)], "mistral", "1")
+                .await;
+        }
+
+        let history = store.get_history(42, None).await.unwrap();
+        assert_eq!(history.verdicts.len(), 2);
+        assert_eq!(history.verdicts[0].value.score, 10);
+        assert_eq!(history.verdicts[1].value.model, "mistral");
+        assert_eq!(store.get_analysis(&identity).await.map(|a| a.score), Some(80));
+    }
+
+    #[test]
+    async fn test_analysis_store_only_records_changed_verdicts() {
+        let store = AnalysisStore::new();
+        let identity = ProcessIdentity::new(42, 1000);
+        for score in [10, 10, 10, 80, 80] {
+            let analysis = ProcessScore {
+                pid: 42,
+                name: "miner".to_string(),
+                score,
+                reason: "test".to_string(),
+            };
+            store
+                .update(vec![(identity.clone(), analysis)], "mistral", "1")
+                .await;
+        }
+
+        let history = store.get_history(42, None).await.unwrap();
+        assert_eq!(history.verdicts.len(), 2);
+        assert_eq!(history.verdicts[0].value.score, 10);
+        assert_eq!(history.verdicts[1].value.score, 80);
+    }
+
+    #[test]
+    async fn test_analysis_store_keeps_current_verdict_of_live_process() {
+        let store = AnalysisStore::new().with_retention(chrono::Duration::milliseconds(50));
+        let identity = ProcessIdentity::new(42, 1000);
+        for _ in 0..2 {
+            let analysis = ProcessScore {
+                pid: 42,
+                name: "miner".to_string(),
+                score: 95,
+                reason: "test".to_string(),
+            };
+            store
+                .update(vec![(identity.clone(), analysis)], "mistral", "1")
+                .await;
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        // The only recorded verdict is past retention but the process is still being analysed
+        assert_eq!(store.get_analysis(&identity).await.map(|a| a.score), Some(95));
+    }
+
+    #[test]
+    async fn test_analysis_store_survives_pid_reuse() {
+        let store = AnalysisStore::new();
+        let old = ProcessIdentity::new(42, 1000);
+        let recycled = ProcessIdentity::new(42, 5000);
+        let analysis = ProcessScore {
+            pid: 42,
+            name: "miner".to_string(),
+            score: 95,
+            reason: "test".to_string(),
+        };
+        store.update(vec![(old.clone(), analysis)], "mistral", "1").await;
+
+        assert!(store.get_analysis(&recycled).await.is_none());
+        assert_eq!(store.get_analysis(&old).await.map(|a| a.score), Some(95));
+        assert!(store.get_history(42, Some(5000)).await.is_none());
+    }
+
+    #[test]
+    async fn test_analysis_store_retention() {
+        let store = AnalysisStore::new().with_retention(chrono::Duration::zero());
+        let analysis = ProcessScore {
+            pid: 42,
+            name: "gone".to_string(),
+            score: 10,
+            reason: "test".to_string(),
+        };
+        let identity = ProcessIdentity::new(42, 1000);
+        store
+            .update(vec![(identity.clone(), analysis)], "mistral", "1")
+            .await;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        store.update(vec![], "mistral", "1").await;
+
+        assert!(store.get_history(42, None).await.is_none());
+        assert!(store.get_analysis(&identity).await.is_none());
+    }
+
+    #[test]
+    async fn test_heuristic_verdicts_join_analysis() {
+        let mut shell = OsProcessInformation::default();
+        shell.pid = 20;
+        shell.identity = ProcessIdentity::new(20, 2000);
+        shell.name = "bash".to_string();
+        shell
+            .attributes
+            .insert(MetadataTags::DevTcpRedirect, "/dev/tcp/10.0.0.1/4444".to_string());
+        let group = OsProcessGroup {
+            parent_process: OsProcessInformation::default(),
+            children: vec![shell],
+        };
+
+        let mut heuristics = command_line_scores(&[group]);
+        assert_eq!(heuristics.len(), 1, "Only the child's command line matched");
+        let heuristic = heuristics.remove(&ProcessIdentity::new(20, 2000)).unwrap();
+        assert_eq!(heuristic.score, 90);
+
+        let analysis = ProcessScore {
+            pid: 20,
+            name: "bash".to_string(),
+            score: 30,
+            reason: "shell".to_string(),
+        };
+        let combined = with_heuristic(analysis, heuristic);
+        assert_eq!(combined.score, 90);
+        assert_eq!(combined.reason, "shell; Command line has a /dev/tcp reverse shell");
+
+        let store = AnalysisStore::new();
+        let identity = ProcessIdentity::new(20, 2000);
+        store
+            .update(vec![(identity.clone(), combined)], HEURISTIC_MODEL, "1")
+            .await;
+        assert_eq!(store.get_history(20, None).await.unwrap().verdicts.len(), 1);
+    }
+
     #[test]
     async fn test_monitor_output_builder() {
         let processes = vec![OsProcessGroup::default()];
@@ -452,12 +1172,15 @@ mod tests {
             .with_cpu(cpu.clone())
             .with_memory(memory.clone())
             .with_disks(disks.clone())
-            .with_networks(network.clone());
+            .with_networks(network.clone())
+            .with_collector("extra", &memory);
 
         assert_eq!(output.processes, processes);
         assert_eq!(output.cpu, cpu);
         assert_eq!(output.memory, memory);
         assert_eq!(output.disks, disks);
         assert_eq!(output.network, network);
+        assert_eq!(output.collector("extra"), Some(memory));
+        assert_eq!(output.collector::<CPUGroup>("extra"), None, "Wrong type");
     }
 }
@@ -5,8 +5,8 @@ use serde_json::json;
 
 use crate::{
     configuration::Settings,
-    monitor::{MonitorOutput, SystemMonitor},
-    os_tooling::process::OsProcessGroup,
+    monitor::MonitorOutput,
+    os_tooling::{command_line::command_line_evidence, process::OsProcessGroup, MetadataTags},
 };
 
 use super::{
@@ -57,15 +57,31 @@ impl OllamaClient {
 
     pub async fn analyze_process_names(
         &self,
-        processess: &Vec<OsProcessGroup>,
+        processess: &[OsProcessGroup],
     ) -> Result<Vec<ProcessScore>> {
         // We probably only need to look at parent threads for now
         // Input object should prob
         let names: Vec<OllamaNameInput> = processess
-            .into_iter()
+            .iter()
             .map(|input| OllamaNameInput {
                 pid: input.parent_process.pid,
                 name: input.parent_process.name.clone(),
+                workload: input
+                    .parent_process
+                    .cgroup
+                    .as_ref()
+                    .and_then(|cgroup| cgroup.workload()),
+                chain: input
+                    .parent_process
+                    .attributes
+                    .get(&MetadataTags::ProcessChain)
+                    .cloned(),
+                lineage: input
+                    .parent_process
+                    .attributes
+                    .get(&MetadataTags::SuspiciousLineage)
+                    .cloned(),
+                evidence: command_line_evidence(&input.parent_process.attributes),
             })
             .collect();
 
@@ -89,19 +105,44 @@ impl OllamaClient {
             },
         };
         let resp = self.make_generate_request(request_body).await?;
-        let json_str = if resp.response.contains("```json") {
-            resp.response
-                .split("```json")  // Split on ```json
-                .nth(1)            // Take everything after ```json
-                .and_then(|s| s.split("```").next())  // Take everything before the next ```
-                .ok_or_else(|| anyhow!("Failed to extract JSON from response"))?
-                .trim()
-        } else {
-            // Fallback - maybe the model just returned raw JSON
-            resp.response.trim()
-        };
-        let scores: Vec<ProcessScore> = serde_json::from_str(json_str)
-            .context("Failed to parse LLM response as ProcessScore array")?;
-        Ok(scores)
+        parse_scores(&resp.response, &input_pids)
+    }
+}
+
+// Scores for pids we never sent are the model making things up, there is nothing to map them to
+fn parse_scores(response: &str, input_pids: &HashSet<u64>) -> Result<Vec<ProcessScore>> {
+    let json_str = if response.contains("```json") {
+        response
+            .split("```json")  // Split on ```json
+            .nth(1)            // Take everything after ```json
+            .and_then(|s| s.split("```").next())  // Take everything before the next ```
+            .ok_or_else(|| anyhow!("Failed to extract JSON from response"))?
+            .trim()
+    } else {
+        // Fallback - maybe the model just returned raw JSON
+        response.trim()
+    };
+    let mut scores: Vec<ProcessScore> = serde_json::from_str(json_str)
+        .context("Failed to parse LLM response as ProcessScore array")?;
+    scores.retain(|score| input_pids.contains(&(score.pid as u64)));
+    Ok(scores)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scores_drops_unknown_pids() {
+        let response = r#"Here you go
+```json
+[{"pid": 1, "name": "systemd", "score": 5, "reason": "init"},
+ {"pid": 999, "name": "ghost", "score": 90, "reason": "made up"}]
+```"#;
+        let scores = parse_scores(response, &HashSet::from([1, 2])).unwrap();
+        assert_eq!(scores.len(), 1);
+        assert_eq!(scores[0].pid, 1);
+
+        assert!(parse_scores("not json", &HashSet::new()).is_err());
     }
 }
@@ -1,4 +1,4 @@
-use std::fmt::Display;
+use std::{collections::BTreeMap, fmt::Display};
 
 use serde_derive::Deserialize;
 use serde_derive::Serialize;
@@ -57,7 +57,19 @@ pub struct Options {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct OllamaNameInput{
     pub pid: u32,
-    pub name: String
+    pub name: String,
+    // Container or systemd unit the process runs in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workload: Option<String>,
+    // Parent chain, e.g. systemd → nginx → bash
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chain: Option<String>,
+    // Lineage rules the chain matched
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lineage: Option<String>,
+    // Command line heuristics that matched, description to the part of the command that did
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub evidence: BTreeMap<String, String>,
 }
 
 impl OllamaNameInput {
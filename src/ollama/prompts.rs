@@ -1,9 +1,16 @@
+// Bump whenever PROCESS_ANALYSIS_PROMPT changes so stored scores can be told apart
+pub const PROCESS_ANALYSIS_PROMPT_VERSION: &str = "4";
+
 pub const PROCESS_ANALYSIS_PROMPT: &str = r#"Score each process name for potential maliciousness on a scale of 0-100.
 
 Input format will be a JSON array of objects containing:
 {
-    "pid": number,    // Process ID
-    "name": string    // Process name
+    "pid": number,       // Process ID
+    "name": string,      // Process name
+    "workload": string,  // Optional, container or systemd unit the process runs in
+    "chain": string,     // Optional, parent chain from the root down to the process
+    "lineage": string,   // Optional, suspicious parent/child patterns the chain matched
+    "evidence": object   // Optional, suspicious command line patterns mapped to the part of the command that matched
 }
 
 Scoring criteria:
@@ -20,6 +27,9 @@ Suspicion factors to consider:
 4. Unusual character usage (.exe on Linux, unusual symbols)
 5. Impersonation of system processes
 6. Cryptocurrency mining related names
+7. Names that don't fit their workload (a shell or miner inside a web server container)
+8. Unusual parents, e.g. nginx → bash → nc or cron → sh → curl, weigh any lineage match heavily
+9. Command line evidence such as a download piped into a shell or a /dev/tcp redirect, weigh it heavily
 
 Output format must be a JSON array of objects with EXACTLY these fields:
 {
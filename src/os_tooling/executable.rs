@@ -0,0 +1,137 @@
+use std::{
+    fs::{self, File},
+    io,
+    os::unix::fs::MetadataExt,
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::cache::Cache;
+
+const DELETED_SUFFIX: &str = " (deleted)";
+// The key changes whenever the binary does, so entries only need to go once nothing runs them
+const HASH_CACHE_TTL_SECONDS: u64 = 24 * 60 * 60;
+const HASH_CACHE_ENTRIES: usize = 4096;
+
+/// The binary a process is running, `deleted` and `replaced` compare it against what is on disk now
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ExecutableInfo {
+    pub path: String,
+    pub sha256: Option<String>,
+    pub deleted: bool,
+    pub replaced: bool,
+}
+
+// device, inode, mtime
+type FileKey = (u64, u64, i64);
+
+/**
+ Resolves and hashes `/proc/<pid>/exe`.
+
+ Hashes are cached by device, inode and mtime of the mapped binary so every process running the
+ same executable is only hashed once. Reading through `/proc/<pid>/exe` hashes what is actually
+ running, even after the file has been deleted.
+**/
+pub struct ExecutableHasher {
+    hashes: Cache<FileKey, String>,
+}
+
+impl ExecutableHasher {
+    pub fn new() -> Self {
+        Self {
+            hashes: Cache::with_capacity(HASH_CACHE_TTL_SECONDS, HASH_CACHE_ENTRIES),
+        }
+    }
+
+    pub fn inspect(&mut self, pid: u32) -> Result<ExecutableInfo> {
+        let exe_link = format!("/proc/{}/exe", pid);
+        let target = fs::read_link(&exe_link)?.to_string_lossy().into_owned();
+        let (path, deleted) = match target.strip_suffix(DELETED_SUFFIX) {
+            Some(path) => (path.to_string(), true),
+            None => (target, false),
+        };
+
+        // Following the link gives the inode that is mapped, not whatever now lives at the path
+        let running = fs::metadata(&exe_link)?;
+        let key = (running.dev(), running.ino(), running.mtime());
+        // The path is in the process's own mount namespace, containers resolve it under their root
+        let replaced = !deleted
+            && is_replaced(&running, fs::metadata(format!("/proc/{}/root{}", pid, path)));
+
+        let sha256 = match self.hashes.get(&key) {
+            Some(hash) => Some(hash),
+            None => match hash_file(&exe_link) {
+                Ok(hash) => {
+                    self.hashes.insert(key, hash.clone());
+                    Some(hash)
+                }
+                Err(e) => {
+                    tracing::debug!("Could not hash {} for {}: {}", path, pid, e);
+                    None
+                }
+            },
+        };
+
+        Ok(ExecutableInfo {
+            path,
+            sha256,
+            deleted,
+            replaced,
+        })
+    }
+}
+
+impl Default for ExecutableHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// A path we can't stat says nothing about whether the binary was swapped, so it isn't replaced
+fn is_replaced(running: &fs::Metadata, on_disk: io::Result<fs::Metadata>) -> bool {
+    match on_disk {
+        Ok(on_disk) => (on_disk.dev(), on_disk.ino()) != (running.dev(), running.ino()),
+        Err(e) => {
+            tracing::debug!("Could not stat executable on disk: {}", e);
+            false
+        }
+    }
+}
+
+pub fn hash_file(path: &str) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inspect_own_executable() {
+        let mut hasher = ExecutableHasher::new();
+        let pid = std::process::id();
+        let info = hasher.inspect(pid).unwrap();
+
+        let expected = std::env::current_exe().unwrap();
+        assert_eq!(info.path, expected.to_string_lossy());
+        assert!(!info.deleted);
+        assert!(!info.replaced);
+        assert_eq!(info.sha256.as_ref().map(|h| h.len()), Some(64));
+
+        // Second lookup is served from the cache
+        assert_eq!(hasher.inspect(pid).unwrap().sha256, info.sha256);
+        assert_eq!(hasher.hashes.stats().hits, 1);
+    }
+
+    #[test]
+    fn test_unreadable_executable_is_not_replaced() {
+        let running = fs::metadata(std::env::current_exe().unwrap()).unwrap();
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert!(!is_replaced(&running, Err(denied)));
+        assert!(is_replaced(&running, fs::metadata("/proc/self/stat")));
+    }
+}
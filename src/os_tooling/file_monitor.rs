@@ -1,55 +1,145 @@
 use std::fs;
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 
+use super::connections::socket_inode;
+
+// Anything open under these is worth surfacing to the analysis
+const SENSITIVE_PREFIXES: [&str; 2] = ["/etc/", "/root/"];
+const SENSITIVE_SEGMENTS: [&str; 1] = ["/.ssh/"];
+const DELETED_SUFFIX: &str = " (deleted)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FdKind {
+    Regular,
+    Socket,
+    Pipe,
+    AnonInode,
+    Memfd,
+    Deleted,
+    Other,
+}
+
+/// Open file descriptors of a process by type, plus any sensitive paths it holds open
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct FileDescriptorSummary {
+    pub total: u64,
+    pub regular: u64,
+    pub sockets: u64,
+    pub pipes: u64,
+    pub anon_inodes: u64,
+    pub memfds: u64,
+    pub deleted: u64,
+    pub other: u64,
+    pub sensitive_paths: Vec<String>,
+    // Joined against the /proc/net tables to find the process's connections
+    #[serde(skip)]
+    pub socket_inodes: Vec<u64>,
+}
+
+impl FileDescriptorSummary {
+    pub fn add(&mut self, target: &str) {
+        self.total += 1;
+        match categorize_fd_target(target) {
+            FdKind::Regular => self.regular += 1,
+            FdKind::Socket => {
+                self.sockets += 1;
+                self.socket_inodes.extend(socket_inode(target));
+            }
+            FdKind::Pipe => self.pipes += 1,
+            FdKind::AnonInode => self.anon_inodes += 1,
+            FdKind::Memfd => self.memfds += 1,
+            FdKind::Deleted => self.deleted += 1,
+            FdKind::Other => self.other += 1,
+        }
+        let path = target.strip_suffix(DELETED_SUFFIX).unwrap_or(target);
+        if is_sensitive_path(path) && !self.sensitive_paths.iter().any(|p| p == path) {
+            self.sensitive_paths.push(path.to_string());
+        }
+    }
+}
 
 pub fn get_process_fd_count(pid: u32) -> Result<u32> {
+    Ok(get_process_fd_summary(pid)?.total as u32)
+}
+
+pub fn get_process_fd_summary(pid: u32) -> Result<FileDescriptorSummary> {
     // Path to the process's fd directory
     let fd_path = format!("/proc/{}/fd", pid);
-    
+
     // Read all file descriptors
     let fd_dir = fs::read_dir(&fd_path)?;
-    let mut open_files = Vec::new();
-    
+    let mut summary = FileDescriptorSummary::default();
+
     // Collect information about each file descriptor
-    for entry in fd_dir {
-        if let Ok(entry) = entry {
-            if let Ok(target) = fs::read_link(entry.path()) {
-                open_files.push(target.to_string_lossy().to_string());
-            }
+    for entry in fd_dir.flatten() {
+        if let Ok(target) = fs::read_link(entry.path()) {
+            summary.add(&target.to_string_lossy());
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Classifies the target of a `/proc/<pid>/fd/<n>` link
+pub fn categorize_fd_target(target: &str) -> FdKind {
+    if target.starts_with("socket:") {
+        FdKind::Socket
+    } else if target.starts_with("pipe:") {
+        FdKind::Pipe
+    } else if target.starts_with("anon_inode:") {
+        FdKind::AnonInode
+    } else if target.starts_with("/memfd:") {
+        // memfds always read as deleted, check them first
+        FdKind::Memfd
+    } else if target.ends_with(DELETED_SUFFIX) {
+        FdKind::Deleted
+    } else if target.starts_with("/dev/") || !target.starts_with('/') {
+        FdKind::Other
+    } else {
+        FdKind::Regular
+    }
+}
+
+pub fn is_sensitive_path(path: &str) -> bool {
+    SENSITIVE_PREFIXES.iter().any(|prefix| path.starts_with(prefix))
+        || SENSITIVE_SEGMENTS.iter().any(|segment| path.contains(segment))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fd_summary_categorizes_targets() {
+        let mut summary = FileDescriptorSummary::default();
+        for target in [
+            "/var/log/syslog",
+            "socket:[81234]",
+            "pipe:[1234]",
+            "anon_inode:[eventfd]",
+            "/memfd:payload (deleted)",
+            "/tmp/dropper (deleted)",
+            "/dev/null",
+            "/etc/shadow",
+            "/home/bob/.ssh/id_rsa",
+            "/etc/shadow",
+        ] {
+            summary.add(target);
         }
+
+        assert_eq!(summary.total, 10);
+        assert_eq!(summary.regular, 4);
+        assert_eq!(summary.sockets, 1);
+        assert_eq!(summary.socket_inodes, vec![81234]);
+        assert_eq!(summary.pipes, 1);
+        assert_eq!(summary.anon_inodes, 1);
+        assert_eq!(summary.memfds, 1);
+        assert_eq!(summary.deleted, 1);
+        assert_eq!(summary.other, 1);
+        assert_eq!(
+            summary.sensitive_paths,
+            vec!["/etc/shadow", "/home/bob/.ssh/id_rsa"]
+        );
     }
-    
-    Ok(open_files.len() as u32)
-   
-}
-
-// pub fn analyze_fd_patterns(history: &[ProcessInfo]) -> HashMap<String, usize> {
-//     let mut patterns = HashMap::new();
-    
-//     // Look for suspicious patterns
-//     for info in history {
-//         // Check for high number of file descriptors
-//         if info.fd_count > 1000 {
-//             *patterns.entry("high_fd_count".to_string()).or_insert(0) += 1;
-//         }
-        
-//         // Check for sensitive file access
-//         for file in &info.open_files {
-//             if file.contains("/etc/") || file.contains("/root/") {
-//                 *patterns.entry("sensitive_files".to_string()).or_insert(0) += 1;
-//             }
-//         }
-        
-//         // Check for many network connections
-//         let network_count = info.open_files
-//             .iter()
-//             .filter(|f| f.contains("socket:"))
-//             .count();
-            
-//         if network_count > 100 {
-//             *patterns.entry("high_network_connections".to_string()).or_insert(0) += 1;
-//         }
-//     }
-    
-//     patterns
-// }
\ No newline at end of file
+}
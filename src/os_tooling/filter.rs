@@ -0,0 +1,166 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use crate::{
+    configuration::{FilterSettings, ProcessMatchSettings},
+    os_tooling::process::OsProcessInformation,
+};
+
+// One side of the filter, a process matches when any rule does
+#[derive(Debug, Clone, Default)]
+struct ProcessMatcher {
+    names: Vec<Regex>,
+    commands: Vec<Regex>,
+    users: Vec<String>,
+    cgroups: Vec<Regex>,
+}
+
+impl ProcessMatcher {
+    fn new(settings: &ProcessMatchSettings) -> Result<Self> {
+        Ok(Self {
+            names: compile(&settings.names)?,
+            commands: compile(&settings.commands)?,
+            users: settings.users.clone(),
+            cgroups: compile(&settings.cgroups)?,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.names.is_empty()
+            && self.commands.is_empty()
+            && self.users.is_empty()
+            && self.cgroups.is_empty()
+    }
+
+    fn matches(&self, process: &OsProcessInformation) -> bool {
+        if self.names.iter().any(|re| re.is_match(&process.name)) {
+            return true;
+        }
+        if !self.commands.is_empty() {
+            let command = process.command.join(" ");
+            if self.commands.iter().any(|re| re.is_match(&command)) {
+                return true;
+            }
+        }
+        if let Some(cgroup) = &process.cgroup {
+            if self.cgroups.iter().any(|re| re.is_match(&cgroup.path)) {
+                return true;
+            }
+        }
+        let user = process
+            .privileges
+            .as_ref()
+            .and_then(|p| p.effective_user.as_deref());
+        self.users
+            .iter()
+            .any(|u| *u == process.user_id || Some(u.as_str()) == user)
+    }
+}
+
+fn compile(patterns: &[String]) -> Result<Vec<Regex>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Regex::new(pattern).with_context(|| format!("Invalid process filter {}", pattern))
+        })
+        .collect()
+}
+
+/// Compiled form of `FilterSettings`, see there for the rules
+#[derive(Debug, Clone)]
+pub struct ProcessFilter {
+    ignore_statuses: Vec<String>,
+    ignore_kernel_threads: bool,
+    include: ProcessMatcher,
+    exclude: ProcessMatcher,
+}
+
+impl ProcessFilter {
+    pub fn new(settings: &FilterSettings) -> Result<Self> {
+        Ok(Self {
+            ignore_statuses: settings.ignore_statuses.clone(),
+            ignore_kernel_threads: settings.ignore_kernel_threads,
+            include: ProcessMatcher::new(&settings.include)?,
+            exclude: ProcessMatcher::new(&settings.exclude)?,
+        })
+    }
+
+    pub fn allows(&self, process: &OsProcessInformation) -> bool {
+        if self.ignore_statuses.iter().any(|s| *s == process.status()) {
+            tracing::debug!("Ignoring process {}, its {}", process.name, process.status());
+            return false;
+        }
+        if self.ignore_kernel_threads && process.is_kernel_thread() {
+            return false;
+        }
+        if !self.include.is_empty() && !self.include.matches(process) {
+            return false;
+        }
+        !self.exclude.matches(process)
+    }
+}
+
+impl Default for ProcessFilter {
+    fn default() -> Self {
+        Self::new(&FilterSettings::default()).expect("Default filters are valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::os_tooling::cgroup::CgroupInfo;
+
+    fn process(name: &str, command: &[&str], cgroup: &str) -> OsProcessInformation {
+        let mut process = OsProcessInformation::default();
+        process.name = name.into();
+        process.command = command.iter().map(|arg| arg.to_string()).collect();
+        process.cgroup = Some(CgroupInfo {
+            path: cgroup.into(),
+            ..Default::default()
+        });
+        process.user_id = "1000".into();
+        process
+    }
+
+    #[test]
+    fn test_process_filter_rules() {
+        let settings = FilterSettings {
+            exclude: ProcessMatchSettings {
+                names: vec!["^chrome".into()],
+                commands: vec!["--type=renderer".into()],
+                users: vec!["1001".into()],
+                cgroups: vec!["backup\\.service$".into()],
+            },
+            ..Default::default()
+        };
+        let filter = ProcessFilter::new(&settings).unwrap();
+        assert!(filter.allows(&process("bash", &["bash"], "/user.slice")));
+        assert!(filter.allows(&process("chromium", &["chromium"], "/")));
+        assert!(!filter.allows(&process("chrome", &["chrome"], "/")));
+        assert!(!filter.allows(&process("node", &["node", "--type=renderer"], "/")));
+        assert!(!filter.allows(&process("tar", &["tar"], "/system.slice/backup.service")));
+
+        let settings = FilterSettings {
+            include: ProcessMatchSettings {
+                users: vec!["1000".into()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let filter = ProcessFilter::new(&settings).unwrap();
+        assert!(filter.allows(&process("bash", &[], "/")));
+        let mut root = process("bash", &[], "/");
+        root.user_id = "0".into();
+        assert!(!filter.allows(&root));
+
+        let settings = FilterSettings {
+            exclude: ProcessMatchSettings {
+                names: vec!["(".into()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(ProcessFilter::new(&settings).is_err());
+    }
+}
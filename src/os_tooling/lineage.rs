@@ -0,0 +1,181 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use crate::{
+    configuration::{LineageRuleSettings, LineageSettings},
+    os_tooling::{
+        process::{OsProcessInformation, ProcessAttribute},
+        MetadataTags,
+    },
+};
+
+const CHAIN_SEPARATOR: &str = " → ";
+
+struct LineageRule {
+    name: String,
+    ancestors: Vec<Regex>,
+    processes: Vec<Regex>,
+    max_depth: usize,
+}
+
+impl LineageRule {
+    fn new(settings: &LineageRuleSettings) -> Result<Self> {
+        Ok(Self {
+            name: settings.name.clone(),
+            ancestors: compile(&settings.ancestors)?,
+            processes: compile(&settings.processes)?,
+            max_depth: settings.max_depth,
+        })
+    }
+
+    // The chain from the matching ancestor down to the process, nearest ancestor wins
+    fn matches(&self, process: &OsProcessInformation) -> Option<String> {
+        if !self.processes.iter().any(|re| re.is_match(&process.name)) {
+            return None;
+        }
+        let depth = process
+            .ancestry
+            .iter()
+            .rev()
+            .take(self.max_depth)
+            .position(|name| self.ancestors.iter().any(|re| re.is_match(name)))?;
+        let start = process.ancestry.len() - 1 - depth;
+        Some(format!(
+            "{}: {}",
+            self.name,
+            chain(&process.ancestry[start..], &process.name)
+        ))
+    }
+}
+
+fn compile(patterns: &[String]) -> Result<Vec<Regex>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Regex::new(pattern).with_context(|| format!("Invalid lineage pattern {}", pattern))
+        })
+        .collect()
+}
+
+fn chain(ancestry: &[String], name: &str) -> String {
+    ancestry
+        .iter()
+        .map(String::as_str)
+        .chain(std::iter::once(name))
+        .collect::<Vec<&str>>()
+        .join(CHAIN_SEPARATOR)
+}
+
+/**
+ Tags every process with its parent chain and flags the ones matching a `LineageSettings` rule.
+
+ Needs `ancestry` filled in, the scanner does that from the process tree before tagging. Ancestors
+ that were filtered out of the scan are not part of the chain.
+**/
+pub struct LineageAttribute {
+    rules: Vec<LineageRule>,
+}
+
+impl LineageAttribute {
+    pub fn new(settings: &LineageSettings) -> Result<Self> {
+        Ok(Self {
+            rules: settings
+                .rules
+                .iter()
+                .map(LineageRule::new)
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl Default for LineageAttribute {
+    fn default() -> Self {
+        Self::new(&LineageSettings::default()).expect("Default lineage rules are valid")
+    }
+}
+
+impl ProcessAttribute for LineageAttribute {
+    fn tag(&self, process: &mut OsProcessInformation) {
+        if process.ancestry.is_empty() {
+            return;
+        }
+        process.attributes.insert(
+            MetadataTags::ProcessChain,
+            chain(&process.ancestry, &process.name),
+        );
+        let matches: Vec<String> = self
+            .rules
+            .iter()
+            .filter_map(|rule| rule.matches(process))
+            .collect();
+        if !matches.is_empty() {
+            process
+                .attributes
+                .insert(MetadataTags::SuspiciousLineage, matches.join("; "));
+        }
+    }
+
+    fn untag(&self, process: &mut OsProcessInformation) {
+        process.attributes.remove(&MetadataTags::ProcessChain);
+        process.attributes.remove(&MetadataTags::SuspiciousLineage);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(chain: &[&str]) -> OsProcessInformation {
+        let (name, ancestry) = chain.split_last().unwrap();
+        let mut process = OsProcessInformation::default();
+        process.name = name.to_string();
+        process.ancestry = ancestry.iter().map(|name| name.to_string()).collect();
+        process
+    }
+
+    fn lineage(attribute: &LineageAttribute, chain: &[&str]) -> Option<String> {
+        let mut process = process(chain);
+        attribute.tag(&mut process);
+        process.attributes.remove(&MetadataTags::SuspiciousLineage)
+    }
+
+    #[test]
+    fn test_lineage_rules() {
+        let attribute = LineageAttribute::default();
+        assert_eq!(
+            lineage(&attribute, &["systemd", "nginx", "bash", "nc"]).as_deref(),
+            Some("service_network_tool: nginx → bash → nc")
+        );
+        assert_eq!(lineage(&attribute, &["systemd", "nginx", "php", "curl"]), None);
+        assert_eq!(
+            lineage(&attribute, &["systemd", "nginx", "nginx", "sh"]).as_deref(),
+            Some("service_shell: nginx → sh")
+        );
+        assert_eq!(
+            lineage(&attribute, &["systemd", "cron", "sh", "curl"]).as_deref(),
+            Some("scheduled_network_tool: cron → sh → curl")
+        );
+        assert_eq!(lineage(&attribute, &["systemd", "sshd", "bash"]), None);
+
+        let mut process = process(&["systemd", "firefox", "python3"]);
+        attribute.tag(&mut process);
+        assert_eq!(
+            process.attributes.get(&MetadataTags::ProcessChain).map(String::as_str),
+            Some("systemd → firefox → python3")
+        );
+        assert!(process.attributes.contains_key(&MetadataTags::SuspiciousLineage));
+
+        // Too far up the chain for a depth of 1
+        let settings = LineageSettings {
+            rules: vec![LineageRuleSettings {
+                name: "direct".into(),
+                ancestors: vec!["^cron$".into()],
+                processes: vec!["^curl$".into()],
+                max_depth: 1,
+            }],
+        };
+        let attribute = LineageAttribute::new(&settings).unwrap();
+        assert_eq!(lineage(&attribute, &["cron", "sh", "curl"]), None);
+        assert!(lineage(&attribute, &["cron", "curl"]).is_some());
+    }
+}
@@ -1,5 +1,15 @@
 pub mod scanner;
 pub mod file_monitor;
+pub mod filter;
+pub mod lineage;
+pub mod connections;
+pub mod executable;
+pub mod cgroup;
+pub mod command_line;
+pub mod namespaces;
+pub mod privileges;
+pub mod proc_connector;
+pub mod procfs;
 pub mod system_resources;
 pub mod resources;
 pub use scanner::*;
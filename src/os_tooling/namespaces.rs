@@ -0,0 +1,92 @@
+use std::fs;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Namespace inodes from `/proc/<pid>/ns`, two processes share a namespace when the inodes match
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Namespaces {
+    pub pid: Option<u64>,
+    pub net: Option<u64>,
+    pub mnt: Option<u64>,
+    pub user: Option<u64>,
+    pub uts: Option<u64>,
+    pub ipc: Option<u64>,
+}
+
+impl Namespaces {
+    /// Kinds where `self` is in the same namespace as `other`, unknown inodes never match
+    pub fn shared_with(&self, other: &Namespaces) -> Vec<&'static str> {
+        self.kinds()
+            .into_iter()
+            .zip(other.kinds())
+            .filter_map(|((kind, ours), (_, theirs))| match (ours, theirs) {
+                (Some(ours), Some(theirs)) if ours == theirs => Some(kind),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn kinds(&self) -> [(&'static str, Option<u64>); 6] {
+        [
+            ("pid", self.pid),
+            ("net", self.net),
+            ("mnt", self.mnt),
+            ("user", self.user),
+            ("uts", self.uts),
+            ("ipc", self.ipc),
+        ]
+    }
+}
+
+// Reading another user's ns links needs ptrace access, those come back as None
+pub fn read_namespaces(pid: u32) -> Result<Namespaces> {
+    let read = |kind: &str| {
+        fs::read_link(format!("/proc/{}/ns/{}", pid, kind))
+            .ok()
+            .and_then(|target| parse_ns_link(&target.to_string_lossy()))
+    };
+    let namespaces = Namespaces {
+        pid: read("pid"),
+        net: read("net"),
+        mnt: read("mnt"),
+        user: read("user"),
+        uts: read("uts"),
+        ipc: read("ipc"),
+    };
+    if namespaces == Namespaces::default() {
+        anyhow::bail!("No readable namespaces for {}", pid);
+    }
+    Ok(namespaces)
+}
+
+/// Inode of a `<kind>:[<inode>]` link target
+pub fn parse_ns_link(target: &str) -> Option<u64> {
+    let (_, inode) = target.split_once(":[")?;
+    inode.strip_suffix(']')?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ns_link_and_sharing() {
+        assert_eq!(parse_ns_link("mnt:[4026531841]"), Some(4026531841));
+        assert_eq!(parse_ns_link("garbage"), None);
+
+        let host = Namespaces {
+            pid: Some(1),
+            mnt: Some(2),
+            net: Some(3),
+            ..Default::default()
+        };
+        let container = Namespaces {
+            pid: Some(10),
+            mnt: Some(2),
+            net: None,
+            ..Default::default()
+        };
+        assert_eq!(container.shared_with(&host), vec!["mnt"]);
+    }
+}
@@ -0,0 +1,224 @@
+use std::{collections::HashMap, fs};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+// linux/capability.h, indexed by bit
+const CAPABILITIES: [&str; 41] = [
+    "CAP_CHOWN",
+    "CAP_DAC_OVERRIDE",
+    "CAP_DAC_READ_SEARCH",
+    "CAP_FOWNER",
+    "CAP_FSETID",
+    "CAP_KILL",
+    "CAP_SETGID",
+    "CAP_SETUID",
+    "CAP_SETPCAP",
+    "CAP_LINUX_IMMUTABLE",
+    "CAP_NET_BIND_SERVICE",
+    "CAP_NET_BROADCAST",
+    "CAP_NET_ADMIN",
+    "CAP_NET_RAW",
+    "CAP_IPC_LOCK",
+    "CAP_IPC_OWNER",
+    "CAP_SYS_MODULE",
+    "CAP_SYS_RAWIO",
+    "CAP_SYS_CHROOT",
+    "CAP_SYS_PTRACE",
+    "CAP_SYS_PACCT",
+    "CAP_SYS_ADMIN",
+    "CAP_SYS_BOOT",
+    "CAP_SYS_NICE",
+    "CAP_SYS_RESOURCE",
+    "CAP_SYS_TIME",
+    "CAP_SYS_TTY_CONFIG",
+    "CAP_MKNOD",
+    "CAP_LEASE",
+    "CAP_AUDIT_WRITE",
+    "CAP_AUDIT_CONTROL",
+    "CAP_SETFCAP",
+    "CAP_MAC_OVERRIDE",
+    "CAP_MAC_ADMIN",
+    "CAP_SYSLOG",
+    "CAP_WAKE_ALARM",
+    "CAP_BLOCK_SUSPEND",
+    "CAP_AUDIT_READ",
+    "CAP_PERFMON",
+    "CAP_BPF",
+    "CAP_CHECKPOINT_RESTORE",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SeccompMode {
+    #[default]
+    Disabled,
+    Strict,
+    Filter,
+}
+
+/**
+ Who a process runs as and what it is allowed to do, from `/proc/<pid>/status`.
+
+ Real and effective ids differ when a setuid/setgid binary is running or the process changed
+ identity without dropping the other. Names are filled in by the scanner from `UserTable`, they stay
+ None for ids without an entry (usually container users).
+**/
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ProcessPrivileges {
+    pub real_uid: u32,
+    pub effective_uid: u32,
+    pub real_gid: u32,
+    pub effective_gid: u32,
+    #[serde(default)]
+    pub real_user: Option<String>,
+    #[serde(default)]
+    pub effective_user: Option<String>,
+    #[serde(default)]
+    pub effective_group: Option<String>,
+    pub setuid: bool,
+    pub setgid: bool,
+    // Raw CapEff mask, `capabilities` is the decoded form
+    pub capability_mask: u64,
+    pub capabilities: Vec<String>,
+    pub no_new_privs: bool,
+    pub seccomp: SeccompMode,
+}
+
+impl ProcessPrivileges {
+    pub fn is_root(&self) -> bool {
+        self.effective_uid == 0
+    }
+
+    pub fn has_capability(&self, name: &str) -> bool {
+        self.capabilities.iter().any(|cap| cap == name)
+    }
+
+    pub fn resolve_names(&mut self, users: &UserTable) {
+        self.real_user = users.user(self.real_uid);
+        self.effective_user = users.user(self.effective_uid);
+        self.effective_group = users.group(self.effective_gid);
+    }
+}
+
+// Status is world readable, unlike most of what we read about other users' processes
+pub fn read_privileges(pid: u32) -> Result<ProcessPrivileges> {
+    let status = fs::read_to_string(format!("/proc/{}/status", pid))?;
+    parse_status(&status).with_context(|| format!("Unexpected status format for {}", pid))
+}
+
+pub fn parse_status(status: &str) -> Option<ProcessPrivileges> {
+    let fields: HashMap<&str, &str> = status
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key, value.trim()))
+        .collect();
+    // Real, effective, saved set and filesystem ids
+    let ids = |key: &str| -> Option<(u32, u32)> {
+        let mut ids = fields.get(key)?.split_whitespace();
+        Some((ids.next()?.parse().ok()?, ids.next()?.parse().ok()?))
+    };
+    let (real_uid, effective_uid) = ids("Uid")?;
+    let (real_gid, effective_gid) = ids("Gid")?;
+    let capability_mask = u64::from_str_radix(fields.get("CapEff")?, 16).ok()?;
+
+    Some(ProcessPrivileges {
+        real_uid,
+        effective_uid,
+        real_gid,
+        effective_gid,
+        setuid: real_uid != effective_uid,
+        setgid: real_gid != effective_gid,
+        capability_mask,
+        capabilities: decode_capabilities(capability_mask),
+        // Both are missing on older kernels
+        no_new_privs: fields.get("NoNewPrivs") == Some(&"1"),
+        seccomp: match fields.get("Seccomp").copied() {
+            Some("1") => SeccompMode::Strict,
+            Some("2") => SeccompMode::Filter,
+            _ => SeccompMode::Disabled,
+        },
+        ..Default::default()
+    })
+}
+
+// Bits newer than this list come back by number rather than being dropped
+pub fn decode_capabilities(mask: u64) -> Vec<String> {
+    (0..64)
+        .filter(|bit| mask & (1 << bit) != 0)
+        .map(|bit| match CAPABILITIES.get(bit) {
+            Some(name) => name.to_string(),
+            None => format!("CAP_{}", bit),
+        })
+        .collect()
+}
+
+/// Uid and gid names from `/etc/passwd` and `/etc/group`
+#[derive(Debug, Clone, Default)]
+pub struct UserTable {
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+}
+
+impl UserTable {
+    // Missing files just mean ids won't be resolved
+    pub fn load() -> Self {
+        let read = |path: &str| fs::read_to_string(path).unwrap_or_default();
+        Self {
+            users: parse_id_file(&read("/etc/passwd")),
+            groups: parse_id_file(&read("/etc/group")),
+        }
+    }
+
+    pub fn user(&self, uid: u32) -> Option<String> {
+        self.users.get(&uid).cloned()
+    }
+
+    pub fn group(&self, gid: u32) -> Option<String> {
+        self.groups.get(&gid).cloned()
+    }
+}
+
+// passwd and group share `name:password:id:...`
+pub fn parse_id_file(contents: &str) -> HashMap<u32, String> {
+    contents
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let id = fields.nth(1)?.parse().ok()?;
+            Some((id, name.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_status_privileges() {
+        let status = "Name:\tpasswd\nUid:\t1000\t0\t0\t0\nGid:\t1000\t1000\t1000\t1000\n\
+                      CapEff:\t0000000000003000\nNoNewPrivs:\t0\nSeccomp:\t2\n";
+        let mut privileges = parse_status(status).unwrap();
+        assert!(privileges.setuid);
+        assert!(!privileges.setgid);
+        assert!(privileges.is_root());
+        assert_eq!(privileges.capabilities, vec!["CAP_NET_ADMIN", "CAP_NET_RAW"]);
+        assert_eq!(privileges.seccomp, SeccompMode::Filter);
+        assert!(!privileges.no_new_privs);
+
+        let users = UserTable {
+            users: parse_id_file("root:x:0:0:root:/root:/bin/bash\nalice:x:1000:1000::/home/alice:/bin/sh"),
+            groups: parse_id_file("alice:x:1000:"),
+        };
+        privileges.resolve_names(&users);
+        assert_eq!(privileges.real_user.as_deref(), Some("alice"));
+        assert_eq!(privileges.effective_user.as_deref(), Some("root"));
+        assert_eq!(privileges.effective_group.as_deref(), Some("alice"));
+
+        assert_eq!(decode_capabilities(1 << 45), vec!["CAP_45"]);
+        assert!(parse_status("Name:\tbroken\n").is_none());
+    }
+}
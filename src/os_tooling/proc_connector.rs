@@ -0,0 +1,249 @@
+use std::{
+    io,
+    mem,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+// linux/connector.h and linux/cn_proc.h
+const CN_IDX_PROC: u32 = 1;
+const CN_VAL_PROC: u32 = 1;
+const PROC_CN_MCAST_LISTEN: u32 = 1;
+const PROC_EVENT_FORK: u32 = 0x0000_0001;
+const PROC_EVENT_EXEC: u32 = 0x0000_0002;
+const PROC_EVENT_EXIT: u32 = 0x8000_0000;
+
+const NLMSG_HDR_LEN: usize = 16;
+const CN_MSG_LEN: usize = 20;
+// what, cpu and timestamp_ns come before the per event data
+const PROC_EVENT_DATA_OFFSET: usize = 16;
+
+/// Process level notifications, thread forks and exits are dropped while parsing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectorEvent {
+    Fork { parent_pid: u32, child_pid: u32 },
+    Exec { pid: u32 },
+    Exit { pid: u32, exit_code: u32 },
+}
+
+/**
+ Subscription to the kernel's process events connector.
+
+ The kernel pushes a message for every fork, exec and exit, so nothing is missed between scans.
+ Subscribing needs CAP_NET_ADMIN, callers are expected to fall back to polling when `open` fails.
+**/
+pub struct ProcConnector {
+    fd: OwnedFd,
+}
+
+impl ProcConnector {
+    pub fn open() -> io::Result<Self> {
+        // SAFETY: socket takes no pointers, the result is checked before it is used
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                libc::NETLINK_CONNECTOR,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: fd was just returned by socket, is valid and nothing else owns it
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // SAFETY: sockaddr_nl is plain integers, all zeroes is a valid value
+        let mut address: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        address.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        address.nl_groups = CN_IDX_PROC;
+        // SAFETY: address lives for the whole call and the length passed is its size
+        let bound = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &address as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if bound < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let connector = Self { fd };
+        connector.send(&listen_message())?;
+        Ok(connector)
+    }
+
+    /// Blocks until the kernel sends something, a receive buffer overrun comes back as no events
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<Vec<ConnectorEvent>> {
+        // SAFETY: the kernel writes at most buf.len() bytes into buf, which we borrow mutably
+        let read = unsafe {
+            libc::recv(
+                self.fd.as_raw_fd(),
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+                0,
+            )
+        };
+        if read < 0 {
+            let error = io::Error::last_os_error();
+            if error.raw_os_error() == Some(libc::ENOBUFS) {
+                tracing::warn!("Process connector overran its buffer, some events were lost");
+                return Ok(Vec::new());
+            }
+            return Err(error);
+        }
+        Ok(parse_messages(&buf[..read as usize]))
+    }
+
+    fn send(&self, message: &[u8]) -> io::Result<()> {
+        // SAFETY: the kernel reads at most message.len() bytes from message, which outlives the call
+        let sent = unsafe {
+            libc::send(
+                self.fd.as_raw_fd(),
+                message.as_ptr() as *const libc::c_void,
+                message.len(),
+                0,
+            )
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+// nlmsghdr + cn_msg + PROC_CN_MCAST_LISTEN
+fn listen_message() -> Vec<u8> {
+    let length = NLMSG_HDR_LEN + CN_MSG_LEN + 4;
+    let mut message = Vec::with_capacity(length);
+    message.extend_from_slice(&(length as u32).to_ne_bytes());
+    message.extend_from_slice(&(libc::NLMSG_DONE as u16).to_ne_bytes());
+    message.extend_from_slice(&0u16.to_ne_bytes());
+    message.extend_from_slice(&0u32.to_ne_bytes());
+    message.extend_from_slice(&std::process::id().to_ne_bytes());
+    message.extend_from_slice(&CN_IDX_PROC.to_ne_bytes());
+    message.extend_from_slice(&CN_VAL_PROC.to_ne_bytes());
+    message.extend_from_slice(&0u32.to_ne_bytes());
+    message.extend_from_slice(&0u32.to_ne_bytes());
+    message.extend_from_slice(&4u16.to_ne_bytes());
+    message.extend_from_slice(&0u16.to_ne_bytes());
+    message.extend_from_slice(&PROC_CN_MCAST_LISTEN.to_ne_bytes());
+    message
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> Option<u32> {
+    let bytes = buf.get(offset..offset + 4)?;
+    Some(u32::from_ne_bytes(bytes.try_into().ok()?))
+}
+
+/// Parses every netlink message in one datagram, anything malformed or uninteresting is skipped
+pub fn parse_messages(buf: &[u8]) -> Vec<ConnectorEvent> {
+    let mut events = Vec::new();
+    let mut offset = 0;
+    while let Some(length) = read_u32(buf, offset) {
+        let length = length as usize;
+        if length < NLMSG_HDR_LEN || offset + length > buf.len() {
+            break;
+        }
+        // Control messages like NLMSG_NOOP or NLMSG_ERROR can be too short to carry a connector message
+        if let Some(data) = buf.get(offset + NLMSG_HDR_LEN + CN_MSG_LEN..offset + length) {
+            if read_u32(buf, offset + NLMSG_HDR_LEN) == Some(CN_IDX_PROC) {
+                events.extend(parse_proc_event(data));
+            }
+        }
+        // Messages are 4 byte aligned
+        offset += (length + 3) & !3;
+    }
+    events
+}
+
+fn parse_proc_event(data: &[u8]) -> Option<ConnectorEvent> {
+    let field = |index: usize| read_u32(data, PROC_EVENT_DATA_OFFSET + index * 4);
+    match read_u32(data, 0)? {
+        PROC_EVENT_FORK => {
+            let (parent_tgid, child_pid, child_tgid) = (field(1)?, field(2)?, field(3)?);
+            // A new thread rather than a new process
+            if child_pid != child_tgid {
+                return None;
+            }
+            Some(ConnectorEvent::Fork {
+                parent_pid: parent_tgid,
+                child_pid,
+            })
+        }
+        PROC_EVENT_EXEC => Some(ConnectorEvent::Exec { pid: field(1)? }),
+        PROC_EVENT_EXIT => {
+            let (pid, tgid) = (field(0)?, field(1)?);
+            if pid != tgid {
+                return None;
+            }
+            Some(ConnectorEvent::Exit {
+                pid,
+                exit_code: field(2)?,
+            })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(what: u32, fields: [u32; 4]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&what.to_ne_bytes());
+        data.extend_from_slice(&0u32.to_ne_bytes());
+        data.extend_from_slice(&0u64.to_ne_bytes());
+        for field in fields {
+            data.extend_from_slice(&field.to_ne_bytes());
+        }
+
+        let mut buf = Vec::new();
+        let length = (NLMSG_HDR_LEN + CN_MSG_LEN + data.len()) as u32;
+        buf.extend_from_slice(&length.to_ne_bytes());
+        buf.extend_from_slice(&[0u8; NLMSG_HDR_LEN - 4]);
+        buf.extend_from_slice(&CN_IDX_PROC.to_ne_bytes());
+        buf.extend_from_slice(&CN_VAL_PROC.to_ne_bytes());
+        buf.extend_from_slice(&[0u8; CN_MSG_LEN - 8]);
+        buf.extend_from_slice(&data);
+        buf
+    }
+
+    #[test]
+    fn test_parse_connector_messages() {
+        let mut buf = message(PROC_EVENT_FORK, [100, 100, 200, 200]);
+        // Thread creation, ignored
+        buf.extend(message(PROC_EVENT_FORK, [100, 100, 201, 100]));
+        buf.extend(message(PROC_EVENT_EXEC, [200, 200, 0, 0]));
+        buf.extend(message(PROC_EVENT_EXIT, [200, 200, 256, 17]));
+
+        assert_eq!(
+            parse_messages(&buf),
+            vec![
+                ConnectorEvent::Fork {
+                    parent_pid: 100,
+                    child_pid: 200
+                },
+                ConnectorEvent::Exec { pid: 200 },
+                ConnectorEvent::Exit {
+                    pid: 200,
+                    exit_code: 256
+                },
+            ]
+        );
+        assert_eq!(listen_message().len(), 40);
+        assert!(parse_messages(&buf[..10]).is_empty());
+    }
+
+    #[test]
+    fn test_parse_skips_header_only_messages() {
+        // An NLMSG_NOOP with nothing after the netlink header
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&(NLMSG_HDR_LEN as u32).to_ne_bytes());
+        buf.extend_from_slice(&[0u8; NLMSG_HDR_LEN - 4]);
+        buf.extend(message(PROC_EVENT_EXEC, [300, 300, 0, 0]));
+
+        assert!(parse_messages(&buf[..NLMSG_HDR_LEN]).is_empty());
+        assert_eq!(parse_messages(&buf), vec![ConnectorEvent::Exec { pid: 300 }]);
+    }
+}
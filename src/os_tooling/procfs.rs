@@ -0,0 +1,151 @@
+use std::fs;
+
+use anyhow::{anyhow, Result};
+
+/// Start time of a process in clock ticks since boot, field 22 of `/proc/<pid>/stat`
+pub fn read_start_time(pid: u32) -> Result<u64> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid))?;
+    parse_stat_start_time(&stat).ok_or_else(|| anyhow!("Malformed /proc/{}/stat", pid))
+}
+
+/// Fields of a `/proc/<pid>/stat` line after the command name, so index 0 is field 3 (state)
+pub fn stat_fields(stat: &str) -> Option<Vec<&str>> {
+    // The command name is wrapped in parens and can itself contain spaces and parens
+    let (_, rest) = stat.rsplit_once(')')?;
+    Some(rest.split_whitespace().collect())
+}
+
+pub fn parse_stat_start_time(stat: &str) -> Option<u64> {
+    stat_fields(stat)?.get(22 - 3)?.parse().ok()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcStat {
+    pub pid: u32,
+    pub name: String,
+    pub state: String,
+    pub parent_pid: u32,
+    pub start_time: u64,
+}
+
+pub fn read_stat(pid: u32) -> Result<ProcStat> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid))?;
+    parse_stat(&stat).ok_or_else(|| anyhow!("Malformed /proc/{}/stat", pid))
+}
+
+pub fn parse_stat(stat: &str) -> Option<ProcStat> {
+    let (pid, rest) = stat.split_once(" (")?;
+    let (name, _) = rest.rsplit_once(')')?;
+    let fields = stat_fields(stat)?;
+    Some(ProcStat {
+        pid: pid.trim().parse().ok()?,
+        name: name.to_string(),
+        state: fields.first()?.to_string(),
+        parent_pid: fields.get(4 - 3)?.parse().ok()?,
+        start_time: fields.get(22 - 3)?.parse().ok()?,
+    })
+}
+
+/// Arguments from `/proc/<pid>/cmdline`, empty for kernel threads and zombies
+pub fn read_cmdline(pid: u32) -> Result<Vec<String>> {
+    let cmdline = fs::read(format!("/proc/{}/cmdline", pid))?;
+    Ok(cmdline
+        .split(|byte| *byte == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect())
+}
+
+// Clock ticks in /proc are USER_HZ, which the kernel fixes at 100 for userspace
+pub const USER_HZ: u64 = 100;
+
+/// Start time in epoch seconds, as sysinfo reports it, converted to clock ticks since boot
+pub fn start_time_from_epoch(start_secs: u64, boot_secs: u64) -> u64 {
+    start_secs.saturating_sub(boot_secs) * USER_HZ
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskStat {
+    pub tid: u32,
+    pub name: String,
+    pub state: String,
+    // utime + stime
+    pub cpu_ticks: u64,
+}
+
+/// Every thread of `pid` from `/proc/<pid>/task`, threads that exit mid read are skipped
+pub fn read_tasks(pid: u32) -> Result<Vec<TaskStat>> {
+    let mut tasks = Vec::new();
+    for entry in fs::read_dir(format!("/proc/{}/task", pid))?.flatten() {
+        let Ok(stat) = fs::read_to_string(entry.path().join("stat")) else {
+            continue;
+        };
+        tasks.extend(parse_task_stat(&stat));
+    }
+    tasks.sort_by_key(|task| task.tid);
+    Ok(tasks)
+}
+
+pub fn parse_task_stat(stat: &str) -> Option<TaskStat> {
+    let (tid, rest) = stat.split_once(" (")?;
+    let (name, _) = rest.rsplit_once(')')?;
+    let fields = stat_fields(stat)?;
+    let ticks = |field: usize| fields.get(field - 3)?.parse::<u64>().ok();
+    Some(TaskStat {
+        tid: tid.trim().parse().ok()?,
+        name: name.to_string(),
+        state: fields.first()?.to_string(),
+        cpu_ticks: ticks(14)? + ticks(15)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stat_start_time() {
+        let stat = "1234 (tmux: server (1)) S 1 1234 1234 0 -1 4194560 2470 0 0 0 \
+                    28 14 0 0 20 0 1 0 8812345 12000000 900 18446744073709551615";
+        assert_eq!(parse_stat_start_time(stat), Some(8812345));
+        assert_eq!(parse_stat_start_time("garbage"), None);
+
+        let parsed = parse_stat(stat).unwrap();
+        assert_eq!(parsed.name, "tmux: server (1)");
+        assert_eq!(parsed.parent_pid, 1);
+        assert_eq!(parsed.start_time, 8812345);
+    }
+
+    #[test]
+    fn test_parse_task_stat() {
+        let stat = "1240 (tokio-runtime-w) S 1 1234 1234 0 -1 4194560 2470 0 0 0 \
+                    28 14 0 0 20 0 1 0 8812345 12000000 900 18446744073709551615";
+        let task = parse_task_stat(stat).unwrap();
+        assert_eq!(task.tid, 1240);
+        assert_eq!(task.name, "tokio-runtime-w");
+        assert_eq!(task.state, "S");
+        assert_eq!(task.cpu_ticks, 42);
+
+        let own = read_tasks(std::process::id()).unwrap();
+        assert!(own.iter().any(|task| task.tid == std::process::id()));
+    }
+
+    #[test]
+    fn test_read_start_time_for_self() {
+        let pid = std::process::id();
+        let start_time = read_start_time(pid).unwrap();
+        assert!(start_time > 0);
+
+        // sysinfo's fallback only has whole seconds
+        let mut system = sysinfo::System::new();
+        let own = sysinfo::Pid::from_u32(pid);
+        system.refresh_processes(sysinfo::ProcessesToUpdate::Some(&[own]), true);
+        let converted = start_time_from_epoch(
+            system.process(own).unwrap().start_time(),
+            sysinfo::System::boot_time(),
+        );
+        assert!(converted.abs_diff(start_time) <= 2 * USER_HZ);
+        assert_eq!(start_time_from_epoch(1_000_050, 1_000_000), 5000);
+        assert_eq!(start_time_from_epoch(10, 20), 0);
+    }
+}
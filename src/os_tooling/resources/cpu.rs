@@ -1,6 +1,10 @@
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
 use sysinfo::{CpuRefreshKind, System};
 
+use crate::collector::Collector;
+
 #[derive(Serialize,Clone, Deserialize,Default,Debug,PartialEq)]
 pub struct CPUGroup {
     pub cpus: Vec<CPUResource>,
@@ -19,14 +23,9 @@ impl CPUResource {
     }
 }
 
+// Usage is measured since the previous call on the same `system`, callers keep it between samples
 pub fn get_current_cpu_usage(system: &mut System) -> CPUGroup {
-
-    system.refresh_specifics(
-        sysinfo::RefreshKind::everything().with_cpu(CpuRefreshKind::everything()),
-    );
-    std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
-    // Refresh CPUs again.
-    system.refresh_cpu_all();
+    system.refresh_cpu_specifics(CpuRefreshKind::everything());
 
     let mut resp: Vec<CPUResource> = vec![];
     for cpu in system.cpus() {
@@ -39,3 +38,38 @@ pub fn get_current_cpu_usage(system: &mut System) -> CPUGroup {
     }
     CPUGroup { cpus: resp }
 }
+
+pub struct CpuCollector {
+    system: System,
+}
+
+impl CpuCollector {
+    pub fn new() -> Self {
+        // Usage is a delta between refreshes, take the baseline now so the first tick has one
+        let mut system = System::new();
+        system.refresh_cpu_all();
+        Self { system }
+    }
+}
+
+impl Default for CpuCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector for CpuCollector {
+    type Output = CPUGroup;
+
+    fn name(&self) -> &'static str {
+        "cpu"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(2)
+    }
+
+    fn collect(&mut self) -> anyhow::Result<CPUGroup> {
+        Ok(get_current_cpu_usage(&mut self.system))
+    }
+}
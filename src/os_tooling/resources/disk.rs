@@ -1,5 +1,9 @@
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
-use sysinfo::{Disks, System};
+use sysinfo::Disks;
+
+use crate::collector::Collector;
 
 #[derive(Clone,Serialize, Default,Deserialize,Debug,PartialEq)]
 pub struct DiskGroup {
@@ -14,11 +18,8 @@ pub struct DiskResource {
     pub usage: f64,
 }
 
-pub fn get_disk_usage(system: &mut System) -> DiskGroup {
-   
-
-    // We display all disks' information:
-
+pub fn get_disk_usage() -> DiskGroup {
+    // We display all disks' information, re-listed every time so new mounts show up
     let disks = Disks::new_with_refreshed_list();
     let mut disk_group: Vec<DiskResource> = vec![];
     for disk in &disks {
@@ -37,3 +38,23 @@ pub fn get_disk_usage(system: &mut System) -> DiskGroup {
 
     DiskGroup { disks: disk_group }
 }
+
+// Disks are re-listed on every collect, nothing to keep between ticks
+#[derive(Default)]
+pub struct DiskCollector;
+
+impl Collector for DiskCollector {
+    type Output = DiskGroup;
+
+    fn name(&self) -> &'static str {
+        "disk"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(10)
+    }
+
+    fn collect(&mut self) -> anyhow::Result<DiskGroup> {
+        Ok(get_disk_usage())
+    }
+}
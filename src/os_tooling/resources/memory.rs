@@ -1,6 +1,10 @@
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
 use sysinfo::System;
 
+use crate::collector::Collector;
+
 #[derive(Serialize, Deserialize,Clone, Debug, Default,PartialEq)]
 pub struct SystemMemory {
     pub total_memory: u64,
@@ -16,7 +20,7 @@ impl SystemMemory {
 }
 
 pub fn get_system_memory(system: &mut System) -> SystemMemory {
-    system.refresh_all();
+    system.refresh_memory();
     SystemMemory {
         total_memory: system.total_memory() / 1024 / 1024,
         used_memory: system.used_memory() / 1024 / 1024,
@@ -24,3 +28,24 @@ pub fn get_system_memory(system: &mut System) -> SystemMemory {
         used_swap: system.used_swap() / 1024 / 1024,
     }
 }
+
+#[derive(Default)]
+pub struct MemoryCollector {
+    system: System,
+}
+
+impl Collector for MemoryCollector {
+    type Output = SystemMemory;
+
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(10)
+    }
+
+    fn collect(&mut self) -> anyhow::Result<SystemMemory> {
+        Ok(get_system_memory(&mut self.system))
+    }
+}
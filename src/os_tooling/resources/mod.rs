@@ -4,6 +4,7 @@ pub mod cpu;
 pub mod memory;
 pub mod network;
 pub mod process;
+pub mod process_tree;
 pub mod disk;
 
 #[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq)]
@@ -17,4 +18,23 @@ pub enum MetadataTags {
     MemoryUsage,
     ThreatScore,
     ThreatScoreReason,
+    TotalSockets,
+    HighFileDescriptors,
+    SensitiveFileAccess,
+    DeletedFileOpen,
+    MemfdOpen,
+    TotalDiskReadRate,
+    TotalDiskWriteRate,
+    DeletedExecutable,
+    ReplacedExecutable,
+    NamespaceEscape,
+    TotalThreads,
+    ProcessChain,
+    SuspiciousLineage,
+    DownloadPipedToShell,
+    EncodedPayload,
+    DevTcpRedirect,
+    InlineSocketScript,
+    TmpExecutable,
+    HexObfuscation,
 }
@@ -1,8 +1,9 @@
-use core::time;
-use std::thread;
+use std::time::{Duration, Instant};
 
 use serde::{Deserialize, Serialize};
-use sysinfo::{Networks, System};
+use sysinfo::Networks;
+
+use crate::collector::Collector;
 
 #[derive(Serialize, Clone,Default, Deserialize, Debug,PartialEq)]
 pub struct NetworkInterfaceGroup {
@@ -12,6 +13,7 @@ pub struct NetworkInterfaceGroup {
 #[derive(Serialize,Clone, Default, Deserialize, Debug,PartialEq)]
 pub struct NetworkInterface {
     pub name: String,
+    // Bytes per second
     pub received: u64,
     pub transmitted: u64,
     pub mac_address: String,
@@ -19,22 +21,23 @@ pub struct NetworkInterface {
     pub packets_transmitted: u64,
 }
 
-pub fn get_network_information(system: &mut System) -> NetworkInterfaceGroup {
-
-    let mut networks = Networks::new_with_refreshed_list();
-    // Waiting a bit to get data from network...
-    thread::sleep(time::Duration::from_secs(2));
-    // Refreshing again to generate diff.
+/**
+ Traffic since the previous call on the same `networks`, which was `elapsed` ago. Received and
+ transmitted are turned into bytes per second, packets stay counts since that call.
+**/
+pub fn get_network_information(networks: &mut Networks, elapsed: Duration) -> NetworkInterfaceGroup {
     networks.refresh(true);
+    let seconds = elapsed.as_secs_f64();
+    let rate = |bytes: u64| if seconds > 0.0 { (bytes as f64 / seconds) as u64 } else { 0 };
 
     let mut interfaces: Vec<NetworkInterface> = vec![];
 
-    for (interface_name, data) in &networks {
+    for (interface_name, data) in networks.iter() {
         
         interfaces.push(NetworkInterface {
             name: interface_name.to_string(),
-            received: data.received(),
-            transmitted: data.transmitted(),
+            received: rate(data.received()),
+            transmitted: rate(data.transmitted()),
             mac_address: data.mac_address().to_string(),
             packets_received: data.packets_received(),
             packets_transmitted: data.packets_transmitted(),
@@ -42,3 +45,42 @@ pub fn get_network_information(system: &mut System) -> NetworkInterfaceGroup {
     }
     NetworkInterfaceGroup { interfaces }
 }
+
+pub struct NetworkCollector {
+    networks: Networks,
+    last_refresh: Instant,
+}
+
+impl NetworkCollector {
+    pub fn new() -> Self {
+        // Traffic is a delta between refreshes, the list doubles as the first baseline
+        Self {
+            networks: Networks::new_with_refreshed_list(),
+            last_refresh: Instant::now(),
+        }
+    }
+}
+
+impl Default for NetworkCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector for NetworkCollector {
+    type Output = NetworkInterfaceGroup;
+
+    fn name(&self) -> &'static str {
+        "network"
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(10)
+    }
+
+    fn collect(&mut self) -> anyhow::Result<NetworkInterfaceGroup> {
+        let elapsed = self.last_refresh.elapsed();
+        self.last_refresh = Instant::now();
+        Ok(get_network_information(&mut self.networks, elapsed))
+    }
+}
@@ -1,37 +1,181 @@
-use std::{collections::HashMap, ffi::OsString};
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    hash::{Hash, Hasher},
+    time::Duration,
+};
 
 use serde::{Deserialize, Serialize};
 
 use std::convert::TryFrom;
 use thiserror::Error;
 
+use crate::os_tooling::{
+    cgroup::{self, CgroupInfo},
+    connections::Connection,
+    executable::ExecutableInfo,
+    file_monitor::FileDescriptorSummary,
+    namespaces::{read_namespaces, Namespaces},
+    privileges::{self, ProcessPrivileges},
+    procfs,
+};
+
 use super::MetadataTags;
 
+/**
+ Stable identity for a process across scans.
+
+ Pids get recycled, pid plus start time doesn't until reboot. The exe hash is informational and
+ not part of equality since it can't always be read.
+**/
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Eq)]
+pub struct ProcessIdentity {
+    pub pid: u32,
+    // Clock ticks since boot from /proc/<pid>/stat
+    pub start_time: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exe_hash: Option<String>,
+}
+
+impl ProcessIdentity {
+    pub fn new(pid: u32, start_time: u64) -> Self {
+        Self {
+            pid,
+            start_time,
+            exe_hash: None,
+        }
+    }
+}
+
+impl PartialEq for ProcessIdentity {
+    fn eq(&self, other: &Self) -> bool {
+        self.pid == other.pid && self.start_time == other.start_time
+    }
+}
+
+impl Hash for ProcessIdentity {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.pid.hash(state);
+        self.start_time.hash(state);
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct OsProcessInformation {
     pub pid: u32,
-    #[serde(skip_serializing)]
+    #[serde(default)]
+    pub identity: ProcessIdentity,
+    #[serde(default)]
+    pub parent_pid: Option<u32>,
+    #[serde(skip_serializing, default)]
     pub cpu: f32,
-    #[serde(skip_serializing)]
+    #[serde(skip_serializing, default)]
     pub memory_usage: u64,
-    #[serde(skip_serializing)]
+    #[serde(skip_serializing, default)]
     pub run_time: u64,
     pub name: String,
-    // exe: String,
-    #[serde(skip_serializing)]
+    // None for kernel threads and processes we aren't allowed to inspect
+    #[serde(default)]
+    pub exe: Option<ExecutableInfo>,
+    #[serde(default)]
+    pub cgroup: Option<CgroupInfo>,
+    #[serde(default)]
+    pub namespaces: Option<Namespaces>,
+    // Only needed to compare against while tagging
+    #[serde(skip)]
+    pub parent_namespaces: Option<Namespaces>,
+    // Ancestor names from the root down to the parent, filled in from the process tree for tagging
+    #[serde(skip)]
+    pub ancestry: Vec<String>,
+    #[serde(skip_serializing, default)]
     status: String,
-    #[serde(skip_serializing)]
+    #[serde(default)]
     pub command: Vec<String>,
-    #[serde(skip_serializing)]
+    // Effective uid, `privileges` has the rest
+    #[serde(default)]
     pub user_id: String,
+    #[serde(default)]
+    pub privileges: Option<ProcessPrivileges>,
     pub attributes: HashMap<MetadataTags, String>,
     pub fd_count: u64,
+    #[serde(default)]
+    pub fds: FileDescriptorSummary,
+    #[serde(default)]
+    pub connections: Vec<Connection>,
+    #[serde(default)]
+    pub disk_io: ProcessDiskIo,
+    #[serde(default)]
+    pub thread_count: usize,
+    #[serde(default)]
+    pub threads: Vec<ThreadInformation>,
+}
+
+// A task in /proc/<pid>/task, cpu is percent of one core since the previous scan
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ThreadInformation {
+    pub tid: u32,
+    pub name: String,
+    pub state: String,
+    pub cpu: f32,
+}
+
+// Totals are since the process started, rates are bytes per second over the last refresh
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct ProcessDiskIo {
+    pub read_bytes: u64,
+    pub written_bytes: u64,
+    pub read_rate: f64,
+    pub write_rate: f64,
+}
+
+impl ProcessDiskIo {
+    pub fn from_usage(usage: sysinfo::DiskUsage, elapsed: Duration) -> Self {
+        let seconds = elapsed.as_secs_f64();
+        let rate = |bytes: u64| if seconds > 0.0 { bytes as f64 / seconds } else { 0.0 };
+        Self {
+            read_bytes: usage.total_read_bytes,
+            written_bytes: usage.total_written_bytes,
+            read_rate: rate(usage.read_bytes),
+            write_rate: rate(usage.written_bytes),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct OsProcessGroup {
     pub parent_process: OsProcessInformation,
-    pub forked_threads: Vec<OsProcessInformation>,
+    // Child processes, threads live on each process
+    #[serde(alias = "forked_threads")]
+    pub children: Vec<OsProcessInformation>,
+}
+
+// Flat per process usage, this is what we keep as process history
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ProcessUsage {
+    pub pid: u32,
+    #[serde(default)]
+    pub start_time: u64,
+    pub name: String,
+    pub cpu: f32,
+    pub memory_usage: u64,
+    #[serde(default)]
+    pub disk_read_rate: f64,
+    #[serde(default)]
+    pub disk_write_rate: f64,
+}
+
+impl From<&OsProcessInformation> for ProcessUsage {
+    fn from(process: &OsProcessInformation) -> Self {
+        Self {
+            pid: process.pid,
+            start_time: process.identity.start_time,
+            name: process.name.clone(),
+            cpu: process.cpu,
+            memory_usage: process.memory_usage,
+            disk_read_rate: process.disk_io.read_rate,
+            disk_write_rate: process.disk_io.write_rate,
+        }
+    }
 }
 
 #[derive(Error, Debug)]
@@ -66,14 +210,33 @@ impl TryFrom<&sysinfo::Process> for OsProcessInformation {
             .iter()
             .map(|os_str| convert_os_string(os_str.clone()))
             .collect();
-        let user_id = process
-            .effective_user_id()
-            .map(|u| u.to_string())
+        let pid = process.pid().as_u32();
+        // Read here rather than refreshed by sysinfo so setuid changes show up straight away
+        let privileges = privileges::read_privileges(pid).ok();
+        let user_id = privileges
+            .as_ref()
+            .map(|p| p.effective_uid.to_string())
             .unwrap_or_default();
+        // Without procfs fall back to sysinfo's start time, converted from epoch seconds to the same
+        // clock ticks since boot, it loses the sub second part
+        let start_time = procfs::read_start_time(pid).unwrap_or_else(|_| {
+            procfs::start_time_from_epoch(process.start_time(), sysinfo::System::boot_time())
+        });
         Ok(Self {
-            pid: process.pid().as_u32(),
+            pid,
+            identity: ProcessIdentity::new(pid, start_time),
+            parent_pid: process.parent().map(|parent| parent.as_u32()),
             // You could make this fallible if needed
             name,
+            exe: None,
+            // Readable for every process, unlike fds and exe
+            cgroup: cgroup::read_cgroup(pid).ok(),
+            namespaces: None,
+            parent_namespaces: None,
+            ancestry: Vec::new(),
+            privileges,
+            thread_count: 0,
+            threads: Vec::new(),
             command: cmd?,
             user_id,
             cpu: process.cpu_usage(),
@@ -83,11 +246,76 @@ impl TryFrom<&sysinfo::Process> for OsProcessInformation {
             status: format!("{:?}", process.status()),
             attributes: HashMap::new(),
             fd_count: 0,
+            fds: FileDescriptorSummary::default(),
+            connections: Vec::new(),
+            // Rates need the refresh interval, the scanner fills them in
+            disk_io: ProcessDiskIo::from_usage(process.disk_usage(), Duration::ZERO),
         })
     }
 }
 
+// Every kernel thread is kthreadd or one of its children
+const KTHREADD_PID: u32 = 2;
+
 impl OsProcessInformation {
+    /**
+     Builds a process straight from /proc, for processes that may be gone before a scan sees them.
+
+     Only what can be read in one go is filled in, there is no cpu or memory usage and the exe is
+     not hashed.
+    **/
+    pub fn from_procfs(pid: u32) -> anyhow::Result<Self> {
+        let stat = procfs::read_stat(pid)?;
+        let exe = std::fs::read_link(format!("/proc/{}/exe", pid))
+            .ok()
+            .map(|target| {
+                let target = target.to_string_lossy();
+                let path = target.strip_suffix(" (deleted)");
+                ExecutableInfo {
+                    path: path.unwrap_or(&target).to_string(),
+                    deleted: path.is_some(),
+                    ..Default::default()
+                }
+            });
+        let privileges = privileges::read_privileges(pid).ok();
+        Ok(Self {
+            pid,
+            identity: ProcessIdentity::new(pid, stat.start_time),
+            parent_pid: Some(stat.parent_pid).filter(|ppid| *ppid != 0),
+            name: stat.name,
+            exe,
+            cgroup: cgroup::read_cgroup(pid).ok(),
+            // Same names sysinfo's ProcessStatus debug output uses
+            status: match stat.state.as_str() {
+                "R" => "Run",
+                "S" => "Sleep",
+                "D" => "UninterruptibleDiskSleep",
+                "Z" => "Zombie",
+                "T" => "Stop",
+                "t" => "Tracing",
+                "X" | "x" => "Dead",
+                "I" => "Idle",
+                _ => "Unknown",
+            }
+            .to_string(),
+            command: procfs::read_cmdline(pid).unwrap_or_default(),
+            user_id: privileges
+                .as_ref()
+                .map(|p| p.effective_uid.to_string())
+                .unwrap_or_default(),
+            privileges,
+            ..Default::default()
+        })
+    }
+
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    pub fn is_kernel_thread(&self) -> bool {
+        self.pid == KTHREADD_PID || self.parent_pid == Some(KTHREADD_PID)
+    }
+
     // This can be useful when you want to handle JSON errors explicitly
     pub fn to_json_string(&self) -> Result<String, serde_json::Error> {
         serde_json::to_string_pretty(self)
@@ -101,6 +329,75 @@ impl OsProcessGroup {
     }
 }
 
+/// Every process in `groups` once, sorted by pid. A parent can also show up as a child of another group
+pub fn unique_processes(groups: &[OsProcessGroup]) -> Vec<&OsProcessInformation> {
+    let mut processes: HashMap<u32, &OsProcessInformation> = HashMap::new();
+    for group in groups {
+        for process in std::iter::once(&group.parent_process).chain(group.children.iter()) {
+            processes.entry(process.pid).or_insert(process);
+        }
+    }
+    let mut processes: Vec<&OsProcessInformation> = processes.into_values().collect();
+    processes.sort_by_key(|p| p.pid);
+    processes
+}
+
+/**
+ Usage fields the API leaves out of a process. Recordings keep them next to each snapshot so a
+ replay serves the same usage the live monitor saw.
+**/
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RecordedUsage {
+    pub pid: u32,
+    pub cpu: f32,
+    pub memory_usage: u64,
+    pub run_time: u64,
+    pub status: String,
+}
+
+impl From<&OsProcessInformation> for RecordedUsage {
+    fn from(process: &OsProcessInformation) -> Self {
+        Self {
+            pid: process.pid,
+            cpu: process.cpu,
+            memory_usage: process.memory_usage,
+            run_time: process.run_time,
+            status: process.status.clone(),
+        }
+    }
+}
+
+impl RecordedUsage {
+    pub fn restore(&self, process: &mut OsProcessInformation) {
+        process.cpu = self.cpu;
+        process.memory_usage = self.memory_usage;
+        process.run_time = self.run_time;
+        process.status = self.status.clone();
+    }
+}
+
+/// Usage for every process in `groups` once, what a recording stores next to the snapshot
+pub fn recorded_usage(groups: &[OsProcessGroup]) -> Vec<RecordedUsage> {
+    unique_processes(groups).into_iter().map(RecordedUsage::from).collect()
+}
+
+/// Puts recorded usage back onto the processes of a replayed snapshot, matched by pid
+pub fn restore_usage(groups: &mut [OsProcessGroup], usage: &[RecordedUsage]) {
+    let usage: HashMap<u32, &RecordedUsage> = usage.iter().map(|u| (u.pid, u)).collect();
+    for group in groups {
+        for process in std::iter::once(&mut group.parent_process).chain(group.children.iter_mut()) {
+            if let Some(recorded) = usage.get(&process.pid) {
+                recorded.restore(process);
+            }
+        }
+    }
+}
+
+/// Flattens groups into one usage entry per pid
+pub fn process_usage(groups: &[OsProcessGroup]) -> Vec<ProcessUsage> {
+    unique_processes(groups).into_iter().map(ProcessUsage::from).collect()
+}
+
 pub trait ProcessAttribute: Send {
     // &mut interior mutability
     fn tag(&self, process: &mut OsProcessInformation);
@@ -147,11 +444,186 @@ impl ProcessAttribute for ResourceUsageAttribute {
     }
 }
 
-pub fn is_process_alive(process: &OsProcessInformation) -> bool {
-    // TODO we should also put these in the config
-    if process.status == "Dead" || process.status == "Idle" {
-        tracing::debug!("Ignoring process {}, its {}", process.name, process.status);
-        return false;
+// Flags processes holding a lot of descriptors or ones that are usually interesting to an analyst
+pub struct FileDescriptorAttribute {
+    fd_threshold: u64,
+}
+impl FileDescriptorAttribute {
+    pub fn new(fd_threshold: u64) -> Self {
+        Self { fd_threshold }
+    }
+}
+impl ProcessAttribute for FileDescriptorAttribute {
+    fn tag(&self, process: &mut OsProcessInformation) {
+        if process.fd_count > self.fd_threshold {
+            process
+                .attributes
+                .insert(MetadataTags::HighFileDescriptors, process.fd_count.to_string());
+        }
+        if !process.fds.sensitive_paths.is_empty() {
+            process.attributes.insert(
+                MetadataTags::SensitiveFileAccess,
+                process.fds.sensitive_paths.join(","),
+            );
+        }
+        if process.fds.deleted > 0 {
+            process
+                .attributes
+                .insert(MetadataTags::DeletedFileOpen, process.fds.deleted.to_string());
+        }
+        if process.fds.memfds > 0 {
+            process
+                .attributes
+                .insert(MetadataTags::MemfdOpen, process.fds.memfds.to_string());
+        }
+    }
+
+    fn untag(&self, process: &mut OsProcessInformation) {
+        process.attributes.remove(&MetadataTags::HighFileDescriptors);
+        process.attributes.remove(&MetadataTags::SensitiveFileAccess);
+        process.attributes.remove(&MetadataTags::DeletedFileOpen);
+        process.attributes.remove(&MetadataTags::MemfdOpen);
+    }
+}
+
+// Running a binary that has since been deleted or swapped out on disk is a classic malware trait
+pub struct ExecutableAttribute;
+impl ProcessAttribute for ExecutableAttribute {
+    fn tag(&self, process: &mut OsProcessInformation) {
+        let Some(exe) = &process.exe else {
+            return;
+        };
+        if exe.deleted {
+            process
+                .attributes
+                .insert(MetadataTags::DeletedExecutable, exe.path.clone());
+        }
+        if exe.replaced {
+            process
+                .attributes
+                .insert(MetadataTags::ReplacedExecutable, exe.path.clone());
+        }
+    }
+
+    fn untag(&self, process: &mut OsProcessInformation) {
+        process.attributes.remove(&MetadataTags::DeletedExecutable);
+        process.attributes.remove(&MetadataTags::ReplacedExecutable);
+    }
+}
+
+// Namespaces a process moving back into the host's is worth flagging for
+const ESCAPE_NAMESPACES: [&str; 4] = ["pid", "mnt", "net", "user"];
+
+/**
+ Flags processes that share a namespace with the host while their parent does not.
+
+ Container processes get their own pid and mnt namespaces, a child that lands back in the host's is
+ what `nsenter -t 1` or a breakout looks like. Host namespaces are read from pid 1, without access
+ to it nothing gets tagged.
+**/
+pub struct NamespaceAttribute {
+    host: Option<Namespaces>,
+}
+impl NamespaceAttribute {
+    pub fn new() -> Self {
+        Self::with_host(read_namespaces(1).ok())
+    }
+
+    pub fn with_host(host: Option<Namespaces>) -> Self {
+        Self { host }
+    }
+}
+impl Default for NamespaceAttribute {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl ProcessAttribute for NamespaceAttribute {
+    fn tag(&self, process: &mut OsProcessInformation) {
+        let (Some(host), Some(namespaces), Some(parent)) =
+            (&self.host, &process.namespaces, &process.parent_namespaces)
+        else {
+            return;
+        };
+        let parent_shared = parent.shared_with(host);
+        let joined: Vec<&str> = namespaces
+            .shared_with(host)
+            .into_iter()
+            .filter(|kind| ESCAPE_NAMESPACES.contains(kind) && !parent_shared.contains(kind))
+            .collect();
+        if !joined.is_empty() {
+            process
+                .attributes
+                .insert(MetadataTags::NamespaceEscape, joined.join(","));
+        }
+    }
+
+    fn untag(&self, process: &mut OsProcessInformation) {
+        process.attributes.remove(&MetadataTags::NamespaceEscape);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recorded_usage_restores_fields_the_api_leaves_out() {
+        let process = OsProcessInformation {
+            pid: 7,
+            cpu: 12.5,
+            run_time: 30,
+            status: "Sleep".to_string(),
+            command: vec!["sleep".to_string(), "60".to_string()],
+            ..OsProcessInformation::default()
+        };
+        let groups = vec![OsProcessGroup {
+            parent_process: process.clone(),
+            children: vec![],
+        }];
+        let api = serde_json::to_value(&groups).unwrap();
+        assert!(api[0]["parent_process"].get("cpu").is_none());
+
+        let mut replayed: Vec<OsProcessGroup> = serde_json::from_value(api).unwrap();
+        assert_eq!(replayed[0].parent_process.cpu, 0.0);
+        restore_usage(&mut replayed, &recorded_usage(&groups));
+        assert_eq!(replayed[0].parent_process, process);
+    }
+
+    #[test]
+    fn test_namespace_attribute_flags_joining_host() {
+        let host = Namespaces {
+            pid: Some(1),
+            mnt: Some(2),
+            net: Some(3),
+            ..Default::default()
+        };
+        let container = Namespaces {
+            pid: Some(10),
+            mnt: Some(20),
+            net: Some(3),
+            ..Default::default()
+        };
+        let attribute = NamespaceAttribute::with_host(Some(host));
+
+        let mut process = OsProcessInformation {
+            namespaces: Some(container),
+            parent_namespaces: Some(container),
+            ..Default::default()
+        };
+        attribute.tag(&mut process);
+        assert!(!process.attributes.contains_key(&MetadataTags::NamespaceEscape));
+
+        // Parent is isolated, child is back in the host's pid and mount namespaces
+        process.namespaces = Some(Namespaces {
+            pid: Some(1),
+            mnt: Some(2),
+            ..container
+        });
+        attribute.tag(&mut process);
+        assert_eq!(
+            process.attributes.get(&MetadataTags::NamespaceEscape).map(String::as_str),
+            Some("pid,mnt")
+        );
     }
-    true
 }
@@ -0,0 +1,201 @@
+use std::collections::{BTreeMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::process::{OsProcessGroup, OsProcessInformation, ProcessIdentity};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ProcessNode {
+    pub identity: ProcessIdentity,
+    pub name: String,
+    pub parent: Option<u32>,
+    pub children: Vec<u32>,
+    // Roots are depth 0
+    pub depth: usize,
+    // Pids from the root down to the direct parent
+    pub ancestry: Vec<u32>,
+}
+
+/**
+ Full parent/child hierarchy of every scanned process.
+
+ `OsProcessGroup` only goes one level deep, the tree keeps the whole chain so something like
+ nginx -> sh -> curl can be followed from either end. Processes whose parent wasn't scanned
+ become roots.
+**/
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ProcessTree {
+    pub roots: Vec<u32>,
+    pub nodes: BTreeMap<u32, ProcessNode>,
+}
+
+impl ProcessTree {
+    pub fn from_processes<'a>(processes: impl IntoIterator<Item = &'a OsProcessInformation>) -> Self {
+        let mut nodes: BTreeMap<u32, ProcessNode> = BTreeMap::new();
+        for process in processes {
+            nodes.entry(process.pid).or_insert_with(|| ProcessNode {
+                identity: process.identity.clone(),
+                name: process.name.clone(),
+                parent: process.parent_pid.filter(|parent| *parent != process.pid),
+                ..ProcessNode::default()
+            });
+        }
+
+        // Parents we never scanned can't anchor anything
+        let pids: HashSet<u32> = nodes.keys().copied().collect();
+        for node in nodes.values_mut() {
+            node.parent = node.parent.filter(|parent| pids.contains(parent));
+        }
+        // Only the processes on a loop lose their parent, anything below the loop keeps its chain
+        for pid in Self::cycle_members(&nodes) {
+            if let Some(node) = nodes.get_mut(&pid) {
+                node.parent = None;
+            }
+        }
+
+        let links: Vec<(u32, u32)> = nodes
+            .iter()
+            .filter_map(|(pid, node)| Some((node.parent?, *pid)))
+            .collect();
+        for (parent, child) in links {
+            if let Some(parent) = nodes.get_mut(&parent) {
+                parent.children.push(child);
+            }
+        }
+
+        let ancestries: Vec<(u32, Vec<u32>)> = nodes
+            .keys()
+            .map(|pid| (*pid, Self::walk_ancestry(&nodes, *pid)))
+            .collect();
+        let mut roots = Vec::new();
+        for (pid, ancestry) in ancestries {
+            let node = nodes.get_mut(&pid).expect("node exists");
+            if node.parent.is_none() {
+                roots.push(pid);
+            }
+            node.depth = ancestry.len();
+            node.ancestry = ancestry;
+        }
+
+        Self { roots, nodes }
+    }
+
+    pub fn from_groups(groups: &[OsProcessGroup]) -> Self {
+        Self::from_processes(
+            groups
+                .iter()
+                .flat_map(|group| std::iter::once(&group.parent_process).chain(&group.children)),
+        )
+    }
+
+    pub fn get(&self, pid: u32) -> Option<&ProcessNode> {
+        self.nodes.get(&pid)
+    }
+
+    /// Process names from the root down to and including `pid`
+    pub fn chain(&self, pid: u32) -> Vec<String> {
+        let Some(node) = self.nodes.get(&pid) else {
+            return vec![];
+        };
+        node.ancestry
+            .iter()
+            .filter_map(|ancestor| self.nodes.get(ancestor))
+            .chain(std::iter::once(node))
+            .map(|node| node.name.clone())
+            .collect()
+    }
+
+    // Parent links have to be loop free by now
+    fn walk_ancestry(nodes: &BTreeMap<u32, ProcessNode>, pid: u32) -> Vec<u32> {
+        let mut ancestry = Vec::new();
+        let mut current = nodes.get(&pid).and_then(|node| node.parent);
+        while let Some(parent) = current {
+            ancestry.push(parent);
+            current = nodes.get(&parent).and_then(|node| node.parent);
+        }
+        ancestry.reverse();
+        ancestry
+    }
+
+    // Pids whose parent links loop back to themselves, each process has one parent so a walk can
+    // only end at a root, at a node an earlier walk finished, or on a loop
+    fn cycle_members(nodes: &BTreeMap<u32, ProcessNode>) -> HashSet<u32> {
+        let mut members = HashSet::new();
+        let mut finished: HashSet<u32> = HashSet::new();
+        for start in nodes.keys() {
+            let mut path: Vec<u32> = Vec::new();
+            let mut on_path: HashSet<u32> = HashSet::new();
+            let mut current = Some(*start);
+            while let Some(pid) = current {
+                if finished.contains(&pid) {
+                    break;
+                }
+                if on_path.contains(&pid) {
+                    let loop_start = path.iter().position(|p| *p == pid).expect("pid is on the path");
+                    members.extend(&path[loop_start..]);
+                    break;
+                }
+                path.push(pid);
+                on_path.insert(pid);
+                current = nodes.get(&pid).and_then(|node| node.parent);
+            }
+            finished.extend(path);
+        }
+        members
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, parent_pid: Option<u32>, name: &str) -> OsProcessInformation {
+        let mut process = OsProcessInformation::default();
+        process.pid = pid;
+        process.parent_pid = parent_pid;
+        process.name = name.to_string();
+        process
+    }
+
+    #[test]
+    fn test_process_tree_keeps_full_chain() {
+        let processes = [
+            process(1, None, "systemd"),
+            process(10, Some(1), "nginx"),
+            process(20, Some(10), "sh"),
+            process(30, Some(20), "curl"),
+            process(40, Some(999), "orphan"),
+        ];
+        let tree = ProcessTree::from_processes(&processes);
+
+        assert_eq!(tree.roots, vec![1, 40]);
+        let curl = tree.get(30).unwrap();
+        assert_eq!(curl.depth, 3);
+        assert_eq!(curl.ancestry, vec![1, 10, 20]);
+        assert_eq!(tree.get(10).unwrap().children, vec![20]);
+        assert_eq!(tree.chain(30), vec!["systemd", "nginx", "sh", "curl"]);
+    }
+
+    #[test]
+    fn test_process_tree_breaks_cycles() {
+        let processes = [
+            process(1, Some(2), "a"),
+            process(2, Some(1), "b"),
+            process(3, Some(1), "c"),
+            process(4, Some(3), "d"),
+        ];
+        let tree = ProcessTree::from_processes(&processes);
+
+        // Only the loop itself is cut, its descendants keep their parents
+        assert_eq!(tree.roots, vec![1, 2]);
+        for pid in [1, 2] {
+            assert_eq!(tree.get(pid).unwrap().parent, None);
+        }
+        assert!(tree.get(2).unwrap().children.is_empty(), "b kept a back edge");
+        assert_eq!(tree.get(1).unwrap().children, vec![3]);
+        let d = tree.get(4).unwrap();
+        assert_eq!(d.parent, Some(3));
+        assert_eq!(d.ancestry, vec![1, 3]);
+        assert_eq!(tree.chain(4), vec!["a", "c", "d"]);
+    }
+}
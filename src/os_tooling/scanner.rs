@@ -1,12 +1,36 @@
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
 
 use metrics::counter;
 
-use sysinfo::{Pid, ProcessRefreshKind, System};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System, ThreadKind, UpdateKind};
 
-use crate::os_tooling::process::{is_process_alive, OsProcessInformation};
+use crate::collector::Collector;
+use crate::os_tooling::{
+    command_line::CommandLineAttribute,
+    connections::ConnectionTable,
+    executable::ExecutableHasher,
+    file_monitor::get_process_fd_summary,
+    filter::ProcessFilter,
+    lineage::LineageAttribute,
+    namespaces::{read_namespaces, Namespaces},
+    privileges::UserTable,
+    procfs::{read_tasks, USER_HZ},
+    process::OsProcessInformation,
+};
 
-use super::{process::{OsProcessGroup, ProcessAttribute, ResourceUsageAttribute}, MetadataTags};
+use super::{
+    process::{
+        ExecutableAttribute, FileDescriptorAttribute, NamespaceAttribute, OsProcessGroup,
+        ProcessAttribute, ProcessDiskIo, ResourceUsageAttribute, ThreadInformation,
+    },
+    process_tree::ProcessTree,
+    MetadataTags,
+};
+
+pub const SCAN_INTERVAL: Duration = Duration::from_secs(5);
 
 ///
 /// I think in order to get the model to act how we want, we need to label things and send it to multiple agents
@@ -16,31 +40,79 @@ use super::{process::{OsProcessGroup, ProcessAttribute, ResourceUsageAttribute},
 /// - High Memory
 /// - High Runtime
 /// - Has Forked/Spawned Processes
+/// - Thread Count
+/// - High File Descriptors, Sensitive/Deleted/Memfd Files Open
+/// - Deleted/Replaced Executable
+/// - Joined Host Namespaces
+/// - Parent Chain and Suspicious Lineage
+/// - Suspicious Command Lines (download | sh, /dev/tcp, encoded payloads, ...)
+///
 /// We want to store this data in a lookup table so that we can do the post processing of the model out here
 /// instead of wasting context windows
-///
-
 pub struct SystemScanner {
     attributes: Vec<Box<dyn ProcessAttribute>>,
+    filter: ProcessFilter,
+    // Kept across scans, sysinfo's cpu usage and disk io are deltas against the previous refresh
+    system: System,
+    // Kept across scans so binaries are only hashed once
+    executables: ExecutableHasher,
+    // Per thread cpu is the tick delta between two scans
+    thread_ticks: HashMap<(u32, u32), u64>,
+    last_scan: Option<Instant>,
+}
+
+// Read once per scan and shared by every process in it
+struct ScanContext {
+    // None on the first scan, there is nothing to compute rates against yet
+    since_last_scan: Option<Duration>,
+    connections: ConnectionTable,
+    namespaces: HashMap<u32, Option<Namespaces>>,
+    thread_ticks: HashMap<(u32, u32), u64>,
+    users: UserTable,
+    // A parent is looked up again for every child, None when the filter excluded it
+    described: HashMap<u32, Option<OsProcessInformation>>,
 }
 
 impl SystemScanner {
     pub fn new() -> Self {
-        let attributes: Vec<Box<dyn ProcessAttribute>> =
-            vec![Box::new(ResourceUsageAttribute::new(60.0, 7200))];
-        Self { attributes }
+        Self {
+            attributes: default_attributes(LineageAttribute::default()),
+            filter: ProcessFilter::default(),
+            system: System::new(),
+            executables: ExecutableHasher::new(),
+            thread_ticks: HashMap::new(),
+            last_scan: None,
+        }
+    }
+
+    pub fn with_filter(mut self, filter: ProcessFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_lineage(mut self, lineage: LineageAttribute) -> Self {
+        self.attributes = default_attributes(lineage);
+        self
     }
 
     fn calculate_total_resource_usage(&self, input: &mut OsProcessGroup) {
         let mut total_cpu = input.parent_process.cpu;
         let mut total_memory = input.parent_process.memory_usage;
         let mut total_fd = input.parent_process.fd_count;
+        let mut total_sockets = input.parent_process.fds.sockets;
+        let mut total_read_rate = input.parent_process.disk_io.read_rate;
+        let mut total_write_rate = input.parent_process.disk_io.write_rate;
+        let mut total_threads = input.parent_process.thread_count;
 
         // Sum up resources from all child processes
-        for child in &input.forked_threads {
+        for child in &input.children {
             total_cpu += child.cpu;
             total_memory += child.memory_usage;
             total_fd += child.fd_count;
+            total_sockets += child.fds.sockets;
+            total_read_rate += child.disk_io.read_rate;
+            total_write_rate += child.disk_io.write_rate;
+            total_threads += child.thread_count;
         }
 
         // Store totals in parent's attributes
@@ -56,13 +128,30 @@ impl SystemScanner {
             .parent_process
             .attributes
             .insert(MetadataTags::TotalFileDescriptors, total_fd.to_string());
+        input
+            .parent_process
+            .attributes
+            .insert(MetadataTags::TotalSockets, total_sockets.to_string());
+        input
+            .parent_process
+            .attributes
+            .insert(MetadataTags::TotalDiskReadRate, total_read_rate.to_string());
+        input
+            .parent_process
+            .attributes
+            .insert(MetadataTags::TotalDiskWriteRate, total_write_rate.to_string());
+        input
+            .parent_process
+            .attributes
+            .insert(MetadataTags::TotalThreads, total_threads.to_string());
     }
 
-    pub fn apply_attributes(&self, input: &mut Vec<OsProcessGroup>) {
+    pub fn apply_attributes(&self, input: &mut [OsProcessGroup]) {
+        add_ancestry(input);
         for p in input.iter_mut() {
             self.calculate_total_resource_usage(p);
             for attribute in &self.attributes {
-                for sp in p.forked_threads.iter_mut() {
+                for sp in p.children.iter_mut() {
                     attribute.tag(sp);
                 }
                 attribute.tag(&mut p.parent_process);
@@ -71,34 +160,69 @@ impl SystemScanner {
     }
     /**
     Scans all running processes on the system and groups them by parent-child relationships.
-    Returns a Vec of OsProcessGroup where each entry contains a parent process and its forked threads.
+    Returns a Vec of OsProcessGroup where each entry contains a parent process and its child processes.
+    Threads are not processes here, each process carries its own from /proc/<pid>/task.
+    CPU, disk and thread rates are measured since the previous scan, so they are all zero on the first one.
+    Children of a process the filter excludes become groups of their own, their parent_pid still
+    points at the excluded parent.
     This helps track process hierarchies and identify related processes.
     */
-    pub fn scan_running_proccess(&self) -> anyhow::Result<Vec<OsProcessGroup>> {
+    pub fn scan_running_proccess(&mut self) -> anyhow::Result<Vec<OsProcessGroup>> {
         tracing::info!("System Monitor scanning");
 
-        let mut agent_output: HashMap<u32, OsProcessGroup> = HashMap::new();
         counter!("scan.run").increment(1);
 
-        let mut sys = System::new_all();
-        // Wait a bit because CPU usage is based on diff.
-        std::thread::sleep(Duration::from_secs(2));
-        // Refresh CPU usage to get actual value.
-        sys.refresh_processes_specifics(
-            sysinfo::ProcessesToUpdate::All,
+        // Only what changes between scans, start time, name, parent and status always come along.
+        // cmd has to be re-read since exec replaces it
+        self.system.refresh_processes_specifics(
+            ProcessesToUpdate::All,
             true,
-            ProcessRefreshKind::nothing().with_cpu(),
+            ProcessRefreshKind::nothing()
+                .with_cpu()
+                .with_memory()
+                .with_disk_usage()
+                .with_cmd(UpdateKind::Always),
         );
+        let refreshed_at = Instant::now();
+        let mut scan = ScanContext {
+            since_last_scan: self.last_scan.map(|last| refreshed_at.duration_since(last)),
+            connections: ConnectionTable::read().unwrap_or_else(|e| {
+                tracing::warn!("Could not read socket tables: {}", e);
+                ConnectionTable::default()
+            }),
+            // Every process also needs its parent's, only read each pid's links once
+            namespaces: HashMap::new(),
+            thread_ticks: HashMap::new(),
+            users: UserTable::load(),
+            described: HashMap::new(),
+        };
+
+        // Taken out for the duration of the scan so describe can borrow the rest of self
+        let system = std::mem::take(&mut self.system);
+        let groups = self.group_processes(&system, &mut scan);
+        self.system = system;
+
+        self.thread_ticks = scan.thread_ticks;
+        self.last_scan = Some(refreshed_at);
+        tracing::info!("System Monitor scanning done");
+        // Convert to Vec more efficiently
+        Ok(groups?.into_values().collect())
+    }
 
+    fn group_processes(
+        &mut self,
+        sys: &System,
+        scan: &mut ScanContext,
+    ) -> anyhow::Result<HashMap<u32, OsProcessGroup>> {
+        let mut agent_output: HashMap<u32, OsProcessGroup> = HashMap::new();
         for process in sys.processes().values() {
-            let formatted_process: OsProcessInformation = process.try_into()?;
-            if !is_process_alive(&formatted_process) {
+            // sysinfo lists userland threads alongside processes, they get attached to their process instead
+            if process.thread_kind() == Some(ThreadKind::Userland) {
                 continue;
             }
-
-            // // Add file descriptors
-            // let fd_count = get_process_fd_count(formatted_process.pid)?;
-            // formatted_process.fd_count = fd_count as u64;
+            let Some(formatted_process) = self.describe(process, scan)? else {
+                continue;
+            };
 
             // Handle process based on whether it has a parent
             if let Some(parent_pid) = process.parent() {
@@ -107,22 +231,35 @@ impl SystemScanner {
                 match sys.process(Pid::from(lookup_key as usize)) {
                     Some(parent_process) if !agent_output.contains_key(&lookup_key) => {
                         // First time seeing this parent, create new entry
-                        let formatted_parent: OsProcessInformation = parent_process.try_into()?;
-                        agent_output.insert(
-                            lookup_key,
-                            OsProcessGroup {
-                                forked_threads: vec![formatted_process],
-                                parent_process: formatted_parent,
-                            },
-                        );
+                        match self.describe(parent_process, scan)? {
+                            Some(formatted_parent) => {
+                                agent_output.insert(
+                                    lookup_key,
+                                    OsProcessGroup {
+                                        children: vec![formatted_process],
+                                        parent_process: formatted_parent,
+                                    },
+                                );
+                            }
+                            // Filtered out parents don't take their children with them, the child
+                            // stands on its own and only its parent_pid still names the parent
+                            None => {
+                                agent_output
+                                    .entry(formatted_process.pid)
+                                    .or_insert(OsProcessGroup {
+                                        children: vec![],
+                                        parent_process: formatted_process,
+                                    });
+                            }
+                        }
                     }
                     Some(_) => {
-                        // Parent exists in map, update forked threads
+                        // Parent exists in map, update child processes
                         agent_output
                             .entry(lookup_key)
-                            .and_modify(|x| x.forked_threads.push(formatted_process.clone()))
+                            .and_modify(|x| x.children.push(formatted_process.clone()))
                             .or_insert(OsProcessGroup {
-                                forked_threads: vec![],
+                                children: vec![],
                                 parent_process: formatted_process,
                             });
                     }
@@ -133,14 +270,170 @@ impl SystemScanner {
                 agent_output
                     .entry(formatted_process.pid)
                     .or_insert(OsProcessGroup {
-                        forked_threads: vec![],
+                        children: vec![],
                         parent_process: formatted_process,
                     });
             }
         }
-        tracing::info!("System Monitor scanning done");
-        // Convert to Vec more efficiently
-        Ok(agent_output.into_values().collect())
+        Ok(agent_output)
+    }
+
+    // Each pid is only described once per scan, later lookups get a copy of the first result
+    fn describe(
+        &mut self,
+        process: &sysinfo::Process,
+        scan: &mut ScanContext,
+    ) -> anyhow::Result<Option<OsProcessInformation>> {
+        let pid = process.pid().as_u32();
+        if let Some(described) = scan.described.get(&pid) {
+            return Ok(described.clone());
+        }
+        let described = self.describe_process(process, scan)?;
+        scan.described.insert(pid, described.clone());
+        Ok(described)
+    }
+
+    // Converts and enriches one process with everything that isn't in sysinfo, None when filtered out.
+    // Filtering only needs what the conversion reads so excluded processes skip the expensive parts
+    fn describe_process(
+        &mut self,
+        process: &sysinfo::Process,
+        scan: &mut ScanContext,
+    ) -> anyhow::Result<Option<OsProcessInformation>> {
+        let mut formatted: OsProcessInformation = process.try_into()?;
+        if let Some(privileges) = &mut formatted.privileges {
+            privileges.resolve_names(&scan.users);
+        }
+        if !self.filter.allows(&formatted) {
+            return Ok(None);
+        }
+        formatted.disk_io = ProcessDiskIo::from_usage(
+            process.disk_usage(),
+            scan.since_last_scan.unwrap_or_default(),
+        );
+        add_descriptors(&mut formatted, &scan.connections);
+        self.add_executable(&mut formatted);
+        add_namespaces(&mut formatted, &mut scan.namespaces);
+        self.add_threads(&mut formatted, scan);
+        Ok(Some(formatted))
+    }
+
+    fn add_threads(&self, process: &mut OsProcessInformation, scan: &mut ScanContext) {
+        let tasks = match read_tasks(process.pid) {
+            Ok(tasks) => tasks,
+            Err(e) => {
+                tracing::debug!("Could not read threads of {}: {}", process.pid, e);
+                return;
+            }
+        };
+        let seconds = scan.since_last_scan.map(|d| d.as_secs_f64()).unwrap_or(0.0);
+        process.thread_count = tasks.len();
+        process.threads = tasks
+            .into_iter()
+            .map(|task| {
+                let key = (process.pid, task.tid);
+                // Unknown or recycled tids have nothing to diff against yet
+                let cpu = match self.thread_ticks.get(&key) {
+                    Some(&previous) if seconds > 0.0 && task.cpu_ticks >= previous => {
+                        ((task.cpu_ticks - previous) as f64 / USER_HZ as f64 / seconds * 100.0) as f32
+                    }
+                    _ => 0.0,
+                };
+                scan.thread_ticks.insert(key, task.cpu_ticks);
+                ThreadInformation {
+                    tid: task.tid,
+                    name: task.name,
+                    state: task.state,
+                    cpu,
+                }
+            })
+            .collect();
+    }
+
+    fn add_executable(&mut self, process: &mut OsProcessInformation) {
+        match self.executables.inspect(process.pid) {
+            Ok(exe) => {
+                process.identity.exe_hash = exe.sha256.clone();
+                process.exe = Some(exe);
+            }
+            Err(e) => tracing::debug!("Could not inspect exe of {}: {}", process.pid, e),
+        }
+    }
+}
+
+fn default_attributes(lineage: LineageAttribute) -> Vec<Box<dyn ProcessAttribute>> {
+    vec![
+        Box::new(ResourceUsageAttribute::new(60.0, 7200)),
+        Box::new(FileDescriptorAttribute::new(1000)),
+        Box::new(ExecutableAttribute),
+        Box::new(NamespaceAttribute::new()),
+        Box::new(lineage),
+        Box::new(CommandLineAttribute::new()),
+    ]
+}
+
+// Groups are only one level deep, the tree has every process's full chain
+fn add_ancestry(groups: &mut [OsProcessGroup]) {
+    let tree = ProcessTree::from_groups(groups);
+    for group in groups.iter_mut() {
+        for process in std::iter::once(&mut group.parent_process).chain(group.children.iter_mut()) {
+            let mut chain = tree.chain(process.pid);
+            chain.pop();
+            process.ancestry = chain;
+        }
+    }
+}
+
+// Other users' fd directories need privileges, those processes just keep an empty summary
+fn add_descriptors(process: &mut OsProcessInformation, connections: &ConnectionTable) {
+    match get_process_fd_summary(process.pid) {
+        Ok(summary) => {
+            process.fd_count = summary.total;
+            process.connections = summary
+                .socket_inodes
+                .iter()
+                .filter_map(|inode| connections.get(*inode).cloned())
+                .collect();
+            process.fds = summary;
+        }
+        Err(e) => tracing::debug!("Could not read fds for {}: {}", process.pid, e),
+    }
+}
+
+fn add_namespaces(
+    process: &mut OsProcessInformation,
+    namespaces: &mut HashMap<u32, Option<Namespaces>>,
+) {
+    process.namespaces = *namespaces
+        .entry(process.pid)
+        .or_insert_with(|| read_namespaces(process.pid).ok());
+    process.parent_namespaces = process.parent_pid.and_then(|parent_pid| {
+        *namespaces
+            .entry(parent_pid)
+            .or_insert_with(|| read_namespaces(parent_pid).ok())
+    });
+}
+
+impl Collector for SystemScanner {
+    type Output = Vec<OsProcessGroup>;
+
+    fn name(&self) -> &'static str {
+        "process"
+    }
+
+    fn interval(&self) -> Duration {
+        SCAN_INTERVAL
+    }
+
+    // Hashing new binaries makes the first scans much slower than the rest
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(60)
+    }
+
+    fn collect(&mut self) -> anyhow::Result<Vec<OsProcessGroup>> {
+        let mut groups = self.scan_running_proccess()?;
+        self.apply_attributes(&mut groups);
+        Ok(groups)
     }
 }
 
@@ -0,0 +1,5 @@
+pub mod rollup;
+pub mod timeseries;
+
+pub use rollup::*;
+pub use timeseries::*;
@@ -0,0 +1,414 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cache::RingBuffer,
+    os_tooling::{
+        cpu::CPUGroup, disk::DiskGroup, memory::SystemMemory, network::NetworkInterfaceGroup,
+    },
+};
+
+use super::{Sample, TimeSeriesStore};
+
+/// Aggregates for one metric over one bucket, keyed by metric name
+pub type RollupMetrics = BTreeMap<String, Aggregate>;
+
+/// Resources that can be reduced to a handful of named numbers for rollups
+pub trait Rollup {
+    fn metrics(&self) -> Vec<(&'static str, f64)>;
+}
+
+pub type RollupFn<T> = fn(&T) -> Vec<(&'static str, f64)>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    OneMinute,
+    FiveMinutes,
+    OneHour,
+}
+
+impl Resolution {
+    pub const ALL: [Resolution; 3] = [
+        Resolution::OneMinute,
+        Resolution::FiveMinutes,
+        Resolution::OneHour,
+    ];
+
+    pub fn from_seconds(seconds: i64) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| r.seconds() == seconds)
+    }
+
+    pub fn seconds(&self) -> i64 {
+        match self {
+            Resolution::OneMinute => 60,
+            Resolution::FiveMinutes => 300,
+            Resolution::OneHour => 3600,
+        }
+    }
+
+    // How many finished buckets we keep, a day of minutes, a week of five minutes and a month of hours
+    fn retained_buckets(&self) -> usize {
+        match self {
+            Resolution::OneMinute => 24 * 60,
+            Resolution::FiveMinutes => 7 * 24 * 12,
+            Resolution::OneHour => 30 * 24,
+        }
+    }
+
+    fn retention(&self) -> Duration {
+        Duration::seconds(self.seconds() * self.retained_buckets() as i64)
+    }
+
+    fn bucket_start(&self, timestamp: DateTime<Utc>) -> DateTime<Utc> {
+        let secs = timestamp.timestamp();
+        let start = secs - secs.rem_euclid(self.seconds());
+        Utc.timestamp_opt(start, 0).single().unwrap_or(timestamp)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Aggregate {
+    pub min: f64,
+    pub max: f64,
+    pub avg: f64,
+    pub p95: f64,
+    pub count: usize,
+}
+
+impl Aggregate {
+    pub fn from_values(values: &[f64]) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        // Nearest rank percentile
+        let p95_rank = ((sorted.len() as f64) * 0.95).ceil() as usize;
+        Self {
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            avg: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            p95: sorted[p95_rank.saturating_sub(1)],
+            count: sorted.len(),
+        }
+    }
+}
+
+struct PendingBucket {
+    start: DateTime<Utc>,
+    values: BTreeMap<String, Vec<f64>>,
+}
+
+impl PendingBucket {
+    fn aggregate(&self) -> Sample<RollupMetrics> {
+        Sample {
+            timestamp: self.start,
+            value: self
+                .values
+                .iter()
+                .map(|(name, values)| (name.clone(), Aggregate::from_values(values)))
+                .collect(),
+        }
+    }
+}
+
+struct RollupLevel {
+    resolution: Resolution,
+    pending: Option<PendingBucket>,
+    buckets: RingBuffer<RollupMetrics>,
+    // Finished buckets are appended here when the rollups are persisted
+    persistence: Option<TimeSeriesStore>,
+}
+
+/**
+ Downsamples a stream of raw samples into 1 minute, 5 minute and 1 hour buckets.
+
+ Each level keeps the raw values of its open bucket so p95 is exact, once a sample lands in the
+ next bucket the open one is reduced to min/max/avg/p95 and moved into a bounded ring buffer.
+**/
+pub struct Rollups {
+    levels: Vec<RollupLevel>,
+}
+
+impl Default for Rollups {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rollups {
+    pub fn new() -> Self {
+        let levels = Resolution::ALL
+            .iter()
+            .map(|resolution| RollupLevel {
+                resolution: *resolution,
+                pending: None,
+                buckets: RingBuffer::new(resolution.retained_buckets()),
+                persistence: None,
+            })
+            .collect();
+        Self { levels }
+    }
+
+    /**
+     Rollups that write every finished bucket next to `store`'s segments and start from the buckets
+     an earlier run left there. Only the bucket still being filled is lost on a restart.
+    **/
+    pub fn open(store: &TimeSeriesStore) -> Result<Self> {
+        let mut rollups = Self::new();
+        let now = Utc::now();
+        for level in &mut rollups.levels {
+            let resolution = level.resolution;
+            let persistence =
+                store.nested(&format!("rollup-{}", resolution.seconds()), resolution.retention())?;
+            for bucket in persistence.range::<RollupMetrics>(now - resolution.retention(), now)? {
+                level.buckets.push(bucket);
+            }
+            level.persistence = Some(persistence);
+        }
+        Ok(rollups)
+    }
+
+    pub fn add(&mut self, timestamp: DateTime<Utc>, metrics: &[(&'static str, f64)]) {
+        for level in &mut self.levels {
+            let start = level.resolution.bucket_start(timestamp);
+            match &level.pending {
+                Some(pending) if start < pending.start => {
+                    tracing::debug!("Dropping late sample for closed {:?} bucket", level.resolution);
+                    continue;
+                }
+                Some(pending) if start > pending.start => {
+                    let finished = level.pending.take().expect("pending bucket").aggregate();
+                    if let Some(persistence) = &mut level.persistence {
+                        if let Err(e) = persistence.append(&finished) {
+                            tracing::error!("Failed to persist {:?} rollup: {}", level.resolution, e);
+                        }
+                    }
+                    level.buckets.push(finished);
+                }
+                _ => {}
+            }
+            let pending = level.pending.get_or_insert_with(|| PendingBucket {
+                start,
+                values: BTreeMap::new(),
+            });
+            for (name, value) in metrics {
+                pending
+                    .values
+                    .entry(name.to_string())
+                    .or_default()
+                    .push(*value);
+            }
+        }
+    }
+
+    pub fn run_maintenance(&mut self) -> Result<()> {
+        for level in &mut self.levels {
+            if let Some(persistence) = &mut level.persistence {
+                persistence.enforce_retention()?;
+                persistence.compact()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Buckets starting between `from` and `to`, including the bucket still being filled
+    pub fn buckets(
+        &self,
+        resolution: Resolution,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Vec<Sample<RollupMetrics>> {
+        let Some(level) = self.levels.iter().find(|l| l.resolution == resolution) else {
+            return vec![];
+        };
+        let from = resolution.bucket_start(from);
+        let mut buckets: Vec<Sample<RollupMetrics>> =
+            level.buckets.range(from, to).cloned().collect();
+        if let Some(pending) = level
+            .pending
+            .as_ref()
+            .filter(|p| p.start >= from && p.start <= to)
+        {
+            buckets.push(pending.aggregate());
+        }
+        buckets
+    }
+}
+
+/**
+ Turns raw samples into the same shape as rollup buckets so callers only deal with one format.
+ Without a step every sample becomes its own point, otherwise samples are grouped into `step`
+ second buckets.
+**/
+pub fn downsample(
+    samples: Vec<Sample<Vec<(&'static str, f64)>>>,
+    step: Option<i64>,
+) -> Vec<Sample<RollupMetrics>> {
+    let Some(step) = step.filter(|step| *step > 0) else {
+        return samples
+            .into_iter()
+            .map(|sample| Sample {
+                timestamp: sample.timestamp,
+                value: sample
+                    .value
+                    .into_iter()
+                    .map(|(name, value)| (name.to_string(), Aggregate::from_values(&[value])))
+                    .collect(),
+            })
+            .collect();
+    };
+
+    let mut buckets: BTreeMap<i64, BTreeMap<String, Vec<f64>>> = BTreeMap::new();
+    for sample in samples {
+        let secs = sample.timestamp.timestamp();
+        let bucket = buckets.entry(secs - secs.rem_euclid(step)).or_default();
+        for (name, value) in sample.value {
+            bucket.entry(name.to_string()).or_default().push(value);
+        }
+    }
+    buckets
+        .into_iter()
+        .filter_map(|(start, values)| {
+            Some(Sample {
+                timestamp: Utc.timestamp_opt(start, 0).single()?,
+                value: values
+                    .iter()
+                    .map(|(name, values)| (name.clone(), Aggregate::from_values(values)))
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+impl Rollup for CPUGroup {
+    fn metrics(&self) -> Vec<(&'static str, f64)> {
+        if self.cpus.is_empty() {
+            return vec![];
+        }
+        let total: f64 = self.cpus.iter().map(|cpu| cpu.usage as f64).sum();
+        vec![("usage", total / self.cpus.len() as f64)]
+    }
+}
+
+impl Rollup for SystemMemory {
+    fn metrics(&self) -> Vec<(&'static str, f64)> {
+        let mut metrics = vec![
+            ("used_memory", self.used_memory as f64),
+            ("used_swap", self.used_swap as f64),
+        ];
+        if self.total_memory > 0 {
+            metrics.push((
+                "usage",
+                self.used_memory as f64 / self.total_memory as f64 * 100.0,
+            ));
+        }
+        metrics
+    }
+}
+
+impl Rollup for DiskGroup {
+    fn metrics(&self) -> Vec<(&'static str, f64)> {
+        let total: f64 = self.disks.iter().map(|disk| disk.total).sum();
+        let used: f64 = self.disks.iter().map(|disk| disk.used).sum();
+        if total <= 0.0 {
+            return vec![];
+        }
+        vec![("used", used), ("usage", used / total * 100.0)]
+    }
+}
+
+impl Rollup for NetworkInterfaceGroup {
+    fn metrics(&self) -> Vec<(&'static str, f64)> {
+        let received: u64 = self.interfaces.iter().map(|i| i.received).sum();
+        let transmitted: u64 = self.interfaces.iter().map(|i| i.transmitted).sum();
+        vec![
+            ("received", received as f64),
+            ("transmitted", transmitted as f64),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_aggregate_from_values() {
+        let values: Vec<f64> = (1..=100).map(|v| v as f64).collect();
+        let aggregate = Aggregate::from_values(&values);
+        assert_eq!(aggregate.min, 1.0);
+        assert_eq!(aggregate.max, 100.0);
+        assert_eq!(aggregate.avg, 50.5);
+        assert_eq!(aggregate.p95, 95.0);
+        assert_eq!(aggregate.count, 100);
+    }
+
+    #[test]
+    fn test_downsample_groups_by_step() {
+        let base = Resolution::OneHour.bucket_start(Utc::now());
+        let samples: Vec<Sample<Vec<(&'static str, f64)>>> = (0..6)
+            .map(|i| Sample {
+                timestamp: base + Duration::seconds(i * 10),
+                value: vec![("cpu", i as f64)],
+            })
+            .collect();
+
+        assert_eq!(downsample(samples.clone(), None).len(), 6);
+        let buckets = downsample(samples, Some(30));
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets[1].value["cpu"].avg, 4.0);
+    }
+
+    #[test]
+    fn test_rollups_close_buckets_per_resolution() {
+        let base = Resolution::OneHour.bucket_start(Utc::now() - Duration::hours(3));
+        let mut rollups = Rollups::new();
+        // One sample every 30 seconds for 10 minutes
+        for i in 0..20 {
+            rollups.add(base + Duration::seconds(i * 30), &[("usage", i as f64)]);
+        }
+
+        let minutes = rollups.buckets(Resolution::OneMinute, base, Utc::now());
+        assert_eq!(minutes.len(), 10);
+        assert_eq!(minutes[0].value["usage"].count, 2);
+        assert_eq!(minutes[9].value["usage"].max, 19.0);
+
+        let five_minutes = rollups.buckets(Resolution::FiveMinutes, base, Utc::now());
+        assert_eq!(five_minutes.len(), 2);
+        assert_eq!(five_minutes[1].value["usage"].min, 10.0);
+
+        let hours = rollups.buckets(Resolution::OneHour, base, Utc::now());
+        assert_eq!(hours.len(), 1);
+        assert_eq!(hours[0].value["usage"].avg, 9.5);
+    }
+
+    #[test]
+    fn test_rollups_survive_reopen() {
+        let dir = std::env::temp_dir().join(format!("odin-rollups-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = TimeSeriesStore::open(&dir, &crate::configuration::StorageSettings::default())
+            .unwrap();
+        let base = Resolution::OneHour.bucket_start(Utc::now() - Duration::hours(3));
+        {
+            let mut rollups = Rollups::open(&store).unwrap();
+            for i in 0..20 {
+                rollups.add(base + Duration::seconds(i * 30), &[("usage", i as f64)]);
+            }
+        }
+
+        // The open bucket of each level was never finished, everything before it was written out
+        let rollups = Rollups::open(&store).unwrap();
+        let minutes = rollups.buckets(Resolution::OneMinute, base, Utc::now());
+        assert_eq!(minutes.len(), 9);
+        assert_eq!(minutes[8].value["usage"].max, 17.0);
+        assert_eq!(rollups.buckets(Resolution::FiveMinutes, base, Utc::now()).len(), 1);
+        assert!(rollups.buckets(Resolution::OneHour, base, Utc::now()).is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,343 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::configuration::StorageSettings;
+
+const SEGMENT_EXTENSION: &str = "seg";
+
+/// A single timestamped value, this is both the on disk record and what range queries return
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Sample<T> {
+    pub timestamp: DateTime<Utc>,
+    pub value: T,
+}
+
+impl<T> Sample<T> {
+    pub fn now(value: T) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            value,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SegmentFile {
+    start: DateTime<Utc>,
+    path: PathBuf,
+    size: u64,
+}
+
+struct ActiveSegment {
+    start: DateTime<Utc>,
+    path: PathBuf,
+    writer: BufWriter<File>,
+    bytes: u64,
+}
+
+/**
+ Append only time series storage for a single resource.
+
+ Samples are written as JSON lines into segment files named after the timestamp of their first
+ sample, so the directory listing is already in time order and segment `n` only holds samples
+ older than the start of segment `n + 1`. That lets retention drop whole segments without reading
+ them. A new segment is always started after a restart so a torn write can only ever affect the
+ tail of a sealed segment, and readers skip lines they can't parse.
+**/
+pub struct TimeSeriesStore {
+    dir: PathBuf,
+    segment_max_bytes: u64,
+    segment_duration: Duration,
+    retention: Duration,
+    active: Option<ActiveSegment>,
+}
+
+impl TimeSeriesStore {
+    pub fn open<P: AsRef<Path>>(dir: P, settings: &StorageSettings) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create storage directory {}", dir.display()))?;
+        Ok(Self {
+            dir,
+            segment_max_bytes: settings.segment_max_bytes,
+            segment_duration: Duration::minutes(settings.segment_duration_minutes as i64),
+            retention: Duration::hours(settings.retention_hours as i64),
+            active: None,
+        })
+    }
+
+    /// Another store in a sub directory with the same segment limits, for data derived from this one
+    pub fn nested(&self, name: &str, retention: Duration) -> Result<Self> {
+        let dir = self.dir.join(name);
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create storage directory {}", dir.display()))?;
+        Ok(Self {
+            dir,
+            segment_max_bytes: self.segment_max_bytes,
+            segment_duration: self.segment_duration,
+            retention,
+            active: None,
+        })
+    }
+
+    pub fn append<T: Serialize>(&mut self, sample: &Sample<T>) -> Result<()> {
+        let mut line = serde_json::to_vec(sample)?;
+        line.push(b'\n');
+
+        let needs_roll = match &self.active {
+            Some(active) => {
+                active.bytes >= self.segment_max_bytes
+                    || sample.timestamp - active.start >= self.segment_duration
+            }
+            None => true,
+        };
+        if needs_roll {
+            self.roll(sample.timestamp)?;
+        }
+
+        let active = self.active.as_mut().expect("active segment after roll");
+        active.writer.write_all(&line)?;
+        // Flush every sample, we write a handful a minute and want readers to see them
+        active.writer.flush()?;
+        active.bytes += line.len() as u64;
+        Ok(())
+    }
+
+    /// Returns every sample with `from <= timestamp <= to`, oldest first
+    pub fn range<T: DeserializeOwned>(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Sample<T>>> {
+        let segments = self.segments()?;
+        let mut samples = Vec::new();
+        for (idx, segment) in segments.iter().enumerate() {
+            let ends_before_from = segments
+                .get(idx + 1)
+                .is_some_and(|next| next.start < from);
+            if segment.start > to || ends_before_from {
+                continue;
+            }
+            for sample in read_segment::<T>(&segment.path)? {
+                if sample.timestamp >= from && sample.timestamp <= to {
+                    samples.push(sample);
+                }
+            }
+        }
+        samples.sort_by_key(|s| s.timestamp);
+        Ok(samples)
+    }
+
+    /// Deletes sealed segments whose newest sample is past retention, returns how many were removed
+    pub fn enforce_retention(&mut self) -> Result<usize> {
+        let cutoff = Utc::now() - self.retention;
+        let segments = self.segments()?;
+        let mut removed = 0;
+        for pair in segments.windows(2) {
+            let (segment, next) = (&pair[0], &pair[1]);
+            if next.start < cutoff && !self.is_active(&segment.path) {
+                fs::remove_file(&segment.path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /**
+     Merges runs of small sealed segments into one file up to `segment_max_bytes`, dropping any
+     expired samples on the way. Returns the number of segment files removed.
+    **/
+    pub fn compact(&mut self) -> Result<usize> {
+        let cutoff = Utc::now() - self.retention;
+        let sealed: Vec<SegmentFile> = self
+            .segments()?
+            .into_iter()
+            .filter(|segment| !self.is_active(&segment.path))
+            .collect();
+
+        let mut groups: Vec<Vec<&SegmentFile>> = Vec::new();
+        let mut group_size = 0;
+        for segment in &sealed {
+            match groups.last_mut() {
+                Some(group) if group_size + segment.size <= self.segment_max_bytes => {
+                    group.push(segment);
+                    group_size += segment.size;
+                }
+                _ => {
+                    groups.push(vec![segment]);
+                    group_size = segment.size;
+                }
+            }
+        }
+
+        let mut removed = 0;
+        for group in groups.into_iter().filter(|group| group.len() > 1) {
+            let target = &group[0].path;
+            let tmp_path = target.with_extension(format!("{}.tmp", SEGMENT_EXTENSION));
+            {
+                let mut writer = BufWriter::new(File::create(&tmp_path)?);
+                for segment in &group {
+                    for sample in read_segment::<serde_json::Value>(&segment.path)? {
+                        if sample.timestamp < cutoff {
+                            continue;
+                        }
+                        serde_json::to_writer(&mut writer, &sample)?;
+                        writer.write_all(b"\n")?;
+                    }
+                }
+                writer.flush()?;
+            }
+            fs::rename(&tmp_path, target)?;
+            for segment in &group[1..] {
+                fs::remove_file(&segment.path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    fn roll(&mut self, start: DateTime<Utc>) -> Result<()> {
+        if let Some(mut active) = self.active.take() {
+            active.writer.flush()?;
+        }
+        let path = self.dir.join(format!(
+            "{:020}.{}",
+            start.timestamp_millis(),
+            SEGMENT_EXTENSION
+        ));
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open segment {}", path.display()))?;
+        let bytes = file.metadata()?.len();
+        self.active = Some(ActiveSegment {
+            start,
+            path,
+            writer: BufWriter::new(file),
+            bytes,
+        });
+        Ok(())
+    }
+
+    fn is_active(&self, path: &Path) -> bool {
+        self.active.as_ref().is_some_and(|active| active.path == path)
+    }
+
+    fn segments(&self) -> Result<Vec<SegmentFile>> {
+        let mut segments = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(SEGMENT_EXTENSION) {
+                continue;
+            }
+            let start = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<i64>().ok())
+                .and_then(|millis| Utc.timestamp_millis_opt(millis).single());
+            let Some(start) = start else {
+                tracing::warn!("Ignoring unexpected file in storage {}", path.display());
+                continue;
+            };
+            let size = fs::metadata(&path)?.len();
+            segments.push(SegmentFile { start, path, size });
+        }
+        segments.sort_by_key(|segment| segment.start);
+        Ok(segments)
+    }
+}
+
+fn read_segment<T: DeserializeOwned>(path: &Path) -> Result<Vec<Sample<T>>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut samples = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(sample) => samples.push(sample),
+            Err(e) => tracing::warn!("Skipping corrupt record in {}: {}", path.display(), e),
+        }
+    }
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("odin-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn sample_at(minutes_ago: i64, value: u32) -> Sample<u32> {
+        Sample {
+            timestamp: Utc::now() - Duration::minutes(minutes_ago),
+            value,
+        }
+    }
+
+    #[test]
+    fn test_history_survives_reopen() {
+        let dir = test_dir("reopen");
+        let settings = StorageSettings::default();
+        {
+            let mut store = TimeSeriesStore::open(&dir, &settings).unwrap();
+            store.append(&sample_at(30, 1)).unwrap();
+            store.append(&sample_at(20, 2)).unwrap();
+            store.append(&sample_at(10, 3)).unwrap();
+        }
+
+        let store = TimeSeriesStore::open(&dir, &settings).unwrap();
+        let values: Vec<u32> = store
+            .range::<u32>(Utc::now() - Duration::minutes(25), Utc::now())
+            .unwrap()
+            .into_iter()
+            .map(|s| s.value)
+            .collect();
+        assert_eq!(values, vec![2, 3]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_retention_and_compaction() {
+        let dir = test_dir("compact");
+        let settings = StorageSettings {
+            retention_hours: 1,
+            segment_duration_minutes: 1,
+            ..StorageSettings::default()
+        };
+        let mut store = TimeSeriesStore::open(&dir, &settings).unwrap();
+        // Every sample is far enough apart to land in its own segment
+        for (minutes_ago, value) in [(180, 1), (120, 2), (50, 3), (40, 4), (30, 5)] {
+            store.append(&sample_at(minutes_ago, value)).unwrap();
+        }
+        assert_eq!(store.segments().unwrap().len(), 5);
+
+        assert_eq!(store.enforce_retention().unwrap(), 1);
+        // The 120 minute segment survives retention because it is followed by a newer one,
+        // compaction merges it with the other sealed segments and drops the expired sample
+        assert_eq!(store.compact().unwrap(), 2);
+        assert_eq!(store.segments().unwrap().len(), 2);
+
+        let values: Vec<u32> = store
+            .range::<u32>(Utc::now() - Duration::hours(4), Utc::now())
+            .unwrap()
+            .into_iter()
+            .map(|s| s.value)
+            .collect();
+        assert_eq!(values, vec![3, 4, 5]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -13,12 +13,10 @@ use tracing_subscriber::{layer::SubscriberExt, EnvFilter, Registry};
  Creates a tracing subscriber configured for OpenTelemetry/Jaeger with flexible log output.
 
  # Arguments
- * `name` - Service name that appears in Jaeger traces
  * `env_filter` - Log level filter (e.g. "info", "debug", "error")
  * `sink` - Where logs are written (e.g. stdout, file). Must implement MakeWriter
 **/
 pub fn get_subscriber<Sink>(
-    name: String,
     env_filter: String,
     sink: Sink,
 ) -> impl Subscriber + Send + Sync
@@ -1,6 +1,10 @@
-use std::{fs::File, io::BufWriter, path::Path};
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
 
-use serde::Serialize;
+use serde::{de::DeserializeOwned, Serialize};
 
 pub async fn write_to_json<T: Serialize, P: AsRef<Path>>(data: &T, path: P) -> std::io::Result<()> {
     // Create file and wrap in buffered writer
@@ -12,3 +16,32 @@ pub async fn write_to_json<T: Serialize, P: AsRef<Path>>(data: &T, path: P) -> s
 
     Ok(())
 }
+
+/// Appends `data` as a single JSON line, creating the file if needed
+pub async fn append_to_json_lines<T: Serialize, P: AsRef<Path>>(
+    data: &T,
+    path: P,
+) -> std::io::Result<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut writer = BufWriter::new(file);
+
+    serde_json::to_writer(&mut writer, data)?;
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Reads back a file written with `append_to_json_lines`, blank lines are skipped
+pub fn read_json_lines<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> std::io::Result<Vec<T>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(serde_json::from_str(&line)?);
+    }
+    Ok(entries)
+}
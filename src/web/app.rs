@@ -9,7 +9,9 @@ use tower_http::cors::{Any, CorsLayer};
 
 use crate::monitor::SystemMonitor;
 
-use super::routes::api::{get_processes,ollama_request};
+use super::routes::api::{
+    get_analysis_history, get_events, get_history, get_processes, ollama_request,
+};
 
 
 
@@ -20,6 +22,9 @@ pub async fn start_server(storage: SystemMonitor) {
         .allow_headers(Any);
     let app = Router::new()
         .route("/metrics", get(get_processes))
+        .route("/history/{resource}", get(get_history))
+        .route("/analysis/{pid}", get(get_analysis_history))
+        .route("/events", get(get_events))
         .route("/ollama", get(ollama_request))
         .layer(cors)
         .with_state(storage);
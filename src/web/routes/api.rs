@@ -1,9 +1,19 @@
 // Implementation to convert reqwest::Response into ApiResponse
-use axum::{extract::State, Json};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
 
 use crate::{
-    monitor::{MonitorOutput, SystemMonitor},
+    events::{EventQuery, ProcessEvent},
+    monitor::{
+        HistoryError, HistoryQuery, HistoryResource, MonitorOutput, ProcessAnalysisHistory,
+        SystemMonitor,
+    },
     ollama::{OllamaClient, ProcessScore},
+    storage::{RollupMetrics, Sample},
 };
 
 pub async fn get_processes(State(monitor): State<SystemMonitor>) -> Json<MonitorOutput> {
@@ -17,3 +27,47 @@ pub async fn ollama_request(State(monitor): State<SystemMonitor>) -> Json<Vec<Pr
     let resp = ollama_client.analyze_system_monitor_output(&snapshot).await.unwrap();
     Json(resp)
 }
+
+pub async fn get_history(
+    State(monitor): State<SystemMonitor>,
+    Path(resource): Path<HistoryResource>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<Vec<Sample<RollupMetrics>>>, (StatusCode, String)> {
+    monitor
+        .get_history(resource, &query)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            let status = match e {
+                HistoryError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                HistoryError::UnknownResource(_) => StatusCode::NOT_FOUND,
+                _ => StatusCode::BAD_REQUEST,
+            };
+            (status, e.to_string())
+        })
+}
+
+#[derive(Deserialize)]
+pub struct AnalysisHistoryQuery {
+    // Picks a specific process when the pid has been reused, defaults to the newest
+    pub start_time: Option<u64>,
+}
+
+pub async fn get_analysis_history(
+    State(monitor): State<SystemMonitor>,
+    Path(pid): Path<u32>,
+    Query(query): Query<AnalysisHistoryQuery>,
+) -> Result<Json<ProcessAnalysisHistory>, (StatusCode, String)> {
+    monitor
+        .get_analysis_history(pid, query.start_time)
+        .await
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, format!("No analysis for pid {}", pid)))
+}
+
+pub async fn get_events(
+    State(monitor): State<SystemMonitor>,
+    Query(query): Query<EventQuery>,
+) -> Json<Vec<Sample<ProcessEvent>>> {
+    Json(monitor.get_events(&query).await)
+}
@@ -3,7 +3,7 @@ use std::process::{Child, Command};
 use std::thread;
 use std::time::Duration;
 /// Different types of test processes we can spawn
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum TestProcessType {
     Idle,   // Just sits there doing nothing
     Sleep,  // Sleeps continuously
@@ -58,7 +58,6 @@ impl Drop for TestProcess {
 /// Test environment that manages multiple test processes
 pub struct TestEnvironment {
     processes: Vec<TestProcess>,
-    process_prefix: String,
 }
 
 impl TestEnvironment {
@@ -73,10 +72,15 @@ impl TestEnvironment {
         // Give processes time to start up
         thread::sleep(Duration::from_millis(500));
 
-        Self {
-            process_prefix,
-            processes,
-        }
+        Self { processes }
+    }
+
+    /// Pid of the spawned process of the given type
+    pub fn pid(&self, process_type: TestProcessType) -> Option<u32> {
+        self.processes
+            .iter()
+            .find(|process| process.process_type == process_type)
+            .map(TestProcess::pid)
     }
 }
 
@@ -1,19 +1,27 @@
 
 // tests/scanner_test.rs
-use odin::monitor::SystemMonitor;
+use odin::monitor::{HistoryError, HistoryQuery, HistoryResource, SystemMonitor};
 
 #[cfg(test)]
 mod tests {
     use std::time::Duration;
 
     use super::*;
-    use odin::configuration::Settings;
+    use crate::helpers::{TestEnvironment, TestProcessType};
+    use chrono::Utc;
+    use odin::{
+        configuration::Settings,
+        monitor::{MonitorOutput, RecordedFrame},
+        os_tooling::{memory::SystemMemory, SystemScanner},
+        storage::Sample,
+        utils::append_to_json_lines,
+    };
     use tokio::test;
 
     #[test]
     async fn test_system_monitor_snapshot() {
         let settings = Settings::default(); // You'll need to implement this
-        let monitor = SystemMonitor::new(settings);
+        let monitor = SystemMonitor::new(settings).unwrap();
 
         // Get initial snapshot
         let snapshot = monitor.get_latest_snapshot().await;
@@ -24,10 +32,88 @@ mod tests {
         assert!(snapshot.disks.disks.is_empty(), "Should have 0 disk info");
     }
 
+    #[test]
+    async fn test_scanner_finds_spawned_processes() {
+        let environment = TestEnvironment::setup("odin_scanner_test_".into());
+
+        let groups = SystemScanner::new()
+            .scan_running_proccess()
+            .expect("Scan should succeed");
+        let pids: Vec<u32> = groups
+            .iter()
+            .flat_map(|group| std::iter::once(&group.parent_process).chain(&group.children))
+            .map(|process| process.pid)
+            .collect();
+
+        for process_type in [TestProcessType::Sleep, TestProcessType::Active] {
+            let pid = environment.pid(process_type).expect("Process should be spawned");
+            assert!(pids.contains(&pid), "Scan should include spawned process {}", pid);
+        }
+    }
+
+    #[test]
+    async fn test_system_monitor_history() {
+        let monitor = SystemMonitor::new(Settings::default()).unwrap();
+
+        let cpu = monitor
+            .get_history(HistoryResource::Cpu, &HistoryQuery::default())
+            .await
+            .expect("CPU history should be available");
+        assert!(cpu.is_empty(), "Nothing collected yet");
+
+        let process = monitor
+            .get_history(HistoryResource::Process, &HistoryQuery::default())
+            .await;
+        assert!(
+            matches!(process, Err(HistoryError::MissingPid)),
+            "Process history needs a pid"
+        );
+    }
+
+    #[test]
+    async fn test_system_monitor_replay() {
+        let path = std::env::temp_dir().join(format!("odin-replay-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        // Recorded a day ago, replay has to keep those timestamps
+        let start = Utc::now() - chrono::Duration::days(1);
+        for (offset, used_memory) in [(0, 100), (2, 200)] {
+            let snapshot = MonitorOutput::new().with_memory(SystemMemory {
+                used_memory,
+                ..SystemMemory::default()
+            });
+            let frame = Sample {
+                timestamp: start + chrono::Duration::seconds(offset),
+                value: RecordedFrame::from(snapshot),
+            };
+            append_to_json_lines(&frame, &path).await.unwrap();
+        }
+
+        let monitor = SystemMonitor::for_replay(Settings::default()).unwrap();
+        monitor
+            .replay(path.clone(), 100.0, false)
+            .await
+            .expect("Replay should finish");
+
+        let snapshot = monitor.get_latest_snapshot().await;
+        assert_eq!(snapshot.memory.used_memory, 200, "Last frame should be live");
+        let query = HistoryQuery {
+            from: Some(start - chrono::Duration::seconds(1)),
+            to: Some(start + chrono::Duration::seconds(5)),
+            ..HistoryQuery::default()
+        };
+        let history = monitor
+            .get_history(HistoryResource::Memory, &query)
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 2, "Both frames should keep their recorded time");
+        assert_eq!(history[0].timestamp, start);
+        std::fs::remove_file(&path).unwrap();
+    }
+
     #[test]
     async fn test_monitor_continuous_updates() {
         let settings = Settings::default();
-        let monitor = SystemMonitor::new(settings);
+        let monitor = SystemMonitor::new(settings).unwrap();
         
         // Spawn the monitor
         let sub_monitor = monitor.clone();